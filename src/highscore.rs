@@ -0,0 +1,72 @@
+use std::{env, io, path::PathBuf};
+
+use crate::storage;
+
+/// The best score seen so far, kept in a small text file between runs.
+pub struct HighScore {
+    best: u32,
+}
+
+impl HighScore {
+    /// Reads the stored high score, creating the file on first run. An
+    /// unreadable or corrupt file counts as no high score rather than an
+    /// error.
+    pub fn load() -> Self {
+        match storage::read(&Self::path()) {
+            Ok(contents) => Self {
+                best: contents.trim().parse().unwrap_or(0),
+            },
+            Err(e) => {
+                let high_score = Self { best: 0 };
+                if e.kind() == io::ErrorKind::NotFound {
+                    if let Err(e) = high_score.save() {
+                        eprintln!("Failed to create high score file: {e}");
+                    }
+                }
+                high_score
+            }
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        storage::write(&Self::path(), &self.best.to_string())
+    }
+
+    /// Records `score` if it beats the current best, returning whether it
+    /// did.
+    pub fn maybe_update(&mut self, score: u32) -> bool {
+        if score > self.best {
+            self.best = score;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn best(&self) -> u32 {
+        self.best
+    }
+
+    #[cfg(windows)]
+    fn path() -> PathBuf {
+        env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(|dir| dir.join("highscore")))
+            .unwrap_or_else(|| PathBuf::from("highscore"))
+    }
+
+    /// In the browser this is only used as the storage key.
+    #[cfg(not(windows))]
+    fn path() -> PathBuf {
+        env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| {
+                env::var_os("HOME")
+                    .map(|home| PathBuf::from(home).join(".local/share"))
+            })
+            .map_or_else(
+                || PathBuf::from("highscore"),
+                |data| data.join("snek/highscore"),
+            )
+    }
+}
@@ -0,0 +1,243 @@
+use std::{collections::HashSet, fmt};
+
+use crate::{GridConfig, Position};
+
+/// Layouts shipped with the game, as their name and file contents.
+const BUNDLED: [(&str, &str); 4] = [
+    ("Box", include_str!("../levels/box.txt")),
+    ("Maze", include_str!("../levels/maze.txt")),
+    ("Pillars", include_str!("../levels/pillars.txt")),
+    ("Rooms", include_str!("../levels/rooms.txt")),
+];
+
+/// A fixed arena layout, read from a text grid where `#` is a wall, `.` is
+/// empty, `S` is where the snake's head starts and `F` is the first fruit.
+pub struct Level {
+    pub name: String,
+    pub walls: HashSet<Position>,
+    pub start: Position,
+    pub fruit: Option<Position>,
+}
+
+#[derive(Debug)]
+pub enum LevelError {
+    UnexpectedChar {
+        line: usize,
+        column: usize,
+        found: char,
+    },
+    /// A second `S` or `F`.
+    DuplicateMarker {
+        line: usize,
+        column: usize,
+        marker: char,
+    },
+    /// A row that isn't as long as the grid is wide.
+    RowLength {
+        line: usize,
+        expected: i32,
+        found: i32,
+    },
+    /// The file has a different number of rows than the grid.
+    RowCount {
+        expected: i32,
+        found: i32,
+    },
+    MissingStart,
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedChar {
+                line,
+                column,
+                found,
+            } => {
+                write!(f, "line {line}, column {column}: unexpected {found:?}")
+            }
+            Self::DuplicateMarker {
+                line,
+                column,
+                marker,
+            } => write!(f, "line {line}, column {column}: second {marker:?}"),
+            Self::RowLength {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {line}: expected {expected} columns, found {found}"
+            ),
+            Self::RowCount { expected, found } => {
+                write!(f, "expected {expected} rows, found {found}")
+            }
+            Self::MissingStart => write!(f, "no start position 'S'"),
+        }
+    }
+}
+
+impl Level {
+    /// Parses a level, which has to be exactly the size of `grid`. Lines
+    /// and columns in errors count from 1.
+    pub fn parse(
+        name: &str,
+        text: &str,
+        grid: &GridConfig,
+    ) -> Result<Self, LevelError> {
+        let mut walls = HashSet::new();
+        let mut start = None;
+        let mut fruit = None;
+
+        let rows: Vec<&str> = text.trim_end().lines().collect();
+        for ((line, y), row) in (1..).zip(0..).zip(&rows) {
+            let mut width = 0;
+
+            for ((column, x), c) in
+                (1..).zip(0..).zip(row.trim_end_matches('\r').chars())
+            {
+                let marker = match c {
+                    '#' => {
+                        walls.insert(Position { x, y });
+                        None
+                    }
+                    '.' => None,
+                    'S' => Some(&mut start),
+                    'F' => Some(&mut fruit),
+                    found => {
+                        return Err(LevelError::UnexpectedChar {
+                            line,
+                            column,
+                            found,
+                        })
+                    }
+                };
+                if let Some(slot) = marker {
+                    if slot.replace(Position { x, y }).is_some() {
+                        return Err(LevelError::DuplicateMarker {
+                            line,
+                            column,
+                            marker: c,
+                        });
+                    }
+                }
+                width = x + 1;
+            }
+
+            if width != grid.width {
+                return Err(LevelError::RowLength {
+                    line,
+                    expected: grid.width,
+                    found: width,
+                });
+            }
+        }
+
+        let height = i32::try_from(rows.len()).unwrap_or(i32::MAX);
+        if height != grid.height {
+            return Err(LevelError::RowCount {
+                expected: grid.height,
+                found: height,
+            });
+        }
+
+        Ok(Self {
+            name: name.to_owned(),
+            walls,
+            start: start.ok_or(LevelError::MissingStart)?,
+            fruit,
+        })
+    }
+
+    /// Every bundled level that fits `grid`. The ones that don't are
+    /// reported and left out rather than stopping the game from starting.
+    pub fn bundled(grid: &GridConfig) -> Vec<Self> {
+        BUNDLED
+            .iter()
+            .filter_map(|(name, text)| {
+                Self::parse(name, text, grid)
+                    .map_err(|e| eprintln!("Skipping level {name}: {e}"))
+                    .ok()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Level, LevelError> {
+        Level::parse("Test", text, &GridConfig::new(3, 2).unwrap())
+    }
+
+    #[test]
+    fn parses_walls_start_and_fruit() {
+        let level = parse("#S.\n.F#\n").unwrap();
+        assert_eq!(level.start, Position { x: 1, y: 0 });
+        assert_eq!(level.fruit, Some(Position { x: 1, y: 1 }));
+        assert_eq!(level.walls.len(), 2);
+    }
+
+    #[test]
+    fn reports_unexpected_char() {
+        assert!(matches!(
+            parse("S..\n.x.\n"),
+            Err(LevelError::UnexpectedChar {
+                line: 2,
+                column: 2,
+                found: 'x',
+            })
+        ));
+    }
+
+    #[test]
+    fn reports_duplicate_marker() {
+        assert!(matches!(
+            parse("S..\n..S\n"),
+            Err(LevelError::DuplicateMarker {
+                line: 2,
+                column: 3,
+                marker: 'S',
+            })
+        ));
+    }
+
+    #[test]
+    fn reports_row_length() {
+        assert!(matches!(
+            parse("S..\n....\n"),
+            Err(LevelError::RowLength {
+                line: 2,
+                expected: 3,
+                found: 4,
+            })
+        ));
+    }
+
+    #[test]
+    fn reports_row_count() {
+        assert!(matches!(
+            parse("S..\n...\n...\n"),
+            Err(LevelError::RowCount {
+                expected: 2,
+                found: 3,
+            })
+        ));
+    }
+
+    #[test]
+    fn reports_missing_start() {
+        assert!(matches!(parse("...\n.F.\n"), Err(LevelError::MissingStart)));
+    }
+
+    #[test]
+    fn bundled_levels_parse() {
+        let grid = GridConfig::default();
+        for (name, text) in BUNDLED {
+            if let Err(e) = Level::parse(name, text, &grid) {
+                panic!("{name}: {e}");
+            }
+        }
+    }
+}
@@ -1,497 +1,4752 @@
 #![windows_subsystem = "windows"]
-use core::panic;
+mod highscore;
+mod level;
+mod pathfinding;
+mod prefs;
+mod save;
+mod storage;
+mod theme;
+
 use std::{
-    cell::RefCell, collections::VecDeque, process::exit, rc::Rc, time::Instant,
+    cell::{BorrowError, BorrowMutError, Cell, RefCell},
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+    ops::{Add, AddAssign, Sub},
+    rc::Rc,
+    sync::OnceLock,
+    time::Duration,
 };
 
 use macroquad::{
+    miniquad::conf::Icon,
     prelude::{
-        is_key_down, is_mouse_button_down, mouse_position, Color, KeyCode,
-        GREEN, RED, WHITE,
+        is_key_down, is_key_pressed, is_mouse_button_down,
+        is_mouse_button_pressed, is_mouse_button_released, mouse_position,
+        touches, vec2, Color, KeyCode, MouseButton, Rect, Touch, TouchPhase,
+        Vec2, WHITE,
+    },
+    shapes::{
+        draw_circle, draw_line, draw_rectangle, draw_rectangle_lines,
+        draw_triangle,
+    },
+    text::{draw_text, measure_text},
+    texture::{
+        draw_texture_ex, DrawTextureParams, FilterMode, Image, Texture2D,
+    },
+    time::{get_frame_time, get_time},
+    window::{
+        clear_background, next_frame, request_new_screen_size, screen_height,
+        screen_width, set_fullscreen,
     },
-    shapes::draw_rectangle,
-    text::draw_text,
-    window::next_frame,
 };
 
-use rand::{self, Rng};
+use rand::{
+    self,
+    seq::{IteratorRandom, SliceRandom},
+    Rng,
+};
+
+use highscore::HighScore;
+use level::Level;
+use prefs::UserPrefs;
+use save::SaveState;
+use serde::{Deserialize, Serialize};
+use theme::{SnakeColors, Theme};
 
 trait Scene {
     fn update(&mut self) -> Option<SwapScene>;
-    fn draw(&self, renderer: &Renderer);
-    fn reset(&mut self);
+    /// Called every frame before `draw`, so nothing from the previous
+    /// frame survives underneath the scene.
+    fn draw_background(&self, renderer: &Renderer) {
+        renderer.clear();
+    }
+    fn draw(&self, renderer: &Renderer, high_score: &HighScore);
+    /// Called when the scene becomes the active one.
+    fn on_enter(&mut self) {}
+    /// Called when another scene is about to take over.
+    fn on_exit(&mut self) {}
+    /// Hands over the settings for the run about to be started.
+    fn set_config(&mut self, _config: &GameConfig) {}
+    /// Hands over the results of the run that just ended, and whether it
+    /// set a new high score.
+    fn set_results(&mut self, _results: &RunResults, _new_record: bool) {}
 }
 
-const GRID_WIDTH: i32 = 20;
-const GRID_HEIGHT: i32 = 20;
-const SCREEN_WIDTH: f32 = 800.;
-const SCREEN_HEIGHT: f32 = 800.;
-const TICK_SPEED_MS: u128 = 250;
+const DEFAULT_GRID_WIDTH: i32 = 20;
+const DEFAULT_GRID_HEIGHT: i32 = 20;
+/// Grid sizes to pick from in settings.
+const GRID_SIZES: [(i32, i32); 4] = [(15, 15), (20, 20), (30, 30), (40, 40)];
+/// Largest grid side accepted from the prefs, `SNEK_GRID` or `--grid`.
+/// Fruit placement scans every cell and the checkerboard is a pixel per
+/// cell, so much bigger grids stall the game.
+const MAX_GRID_SIZE: i32 = 100;
+const DEFAULT_SCREEN_WIDTH: u16 = 800;
+const DEFAULT_SCREEN_HEIGHT: u16 = 800;
+const BASE_TICK_MS: u128 = 250;
+const SPEED_INCREMENT_PER_SCORE: u128 = 5;
+const MIN_TICK_MS: u128 = 60;
+/// Ticks between the play area shrinking, when it does.
+const SHRINK_INTERVAL_TICKS: u32 = 30;
+/// The play area stops shrinking at this many cells across.
+const SHRINK_MIN_SIZE: i32 = 5;
+/// Cells ahead of the snake's start that random walls stay out of.
+const OBSTACLE_CLEARANCE: i32 = 3;
+/// Most ticks run in one frame; any more time owed than that is dropped.
+const MAX_TICKS_PER_FRAME: u32 = 5;
+const STAR_COUNT: usize = 200;
+/// How long a fresh fruit is only telegraphed for before it can be eaten.
+const FRUIT_TELEGRAPH_MS: u128 = 500;
+const INPUT_QUEUE_CAPACITY: usize = 3;
+/// Pixels a finger has to move for a touch to count as a swipe.
+const SWIPE_MIN_DISTANCE: f32 = 30.;
+/// Fruit eaten within this long of the last one keeps the combo going.
+const COMBO_WINDOW_MS: u128 = 3000;
+/// Shown one after the other at the start of a run. The snake sets off as
+/// the last one comes up.
+const COUNTDOWN_LABELS: [&str; 4] = ["3", "2", "1", "GO"];
+const COUNTDOWN_STEP_MS: u128 = 600;
+const MAX_FRUIT_COUNT: usize = 5;
+/// One in this many fruit spawns golden.
+const GOLDEN_FRUIT_CHANCE: u32 = 8;
+const GOLDEN_FRUIT_MULTIPLIER: u32 = 5;
+/// Ticks a golden fruit stays up before it turns back into a normal one.
+const GOLDEN_FRUIT_TICKS: u32 = 30;
+/// Golden fruit starts blinking once this few ticks are left.
+const GOLDEN_FRUIT_BLINK_TICKS: u32 = 10;
+/// Every this many fruit eaten brings out a poison fruit, unless
+/// `snek.toml` says otherwise.
+const DEFAULT_POISON_FRUIT_EVERY: u32 = 5;
+const POISON_FRUIT_PENALTY: u32 = 3;
+/// Ticks a poison fruit stays up before it disappears.
+const POISON_FRUIT_LIFETIME: u32 = 10;
+/// Ticks between speed boosts showing up, while none is out or active.
+const SPEEDBOOST_INTERVAL_TICKS: u32 = 20;
+/// Ticks a speed boost stays up before it disappears.
+const SPEEDBOOST_LIFETIME: u32 = 15;
+/// Ticks a speed boost doubles the speed for once eaten.
+const SPEEDBOOST_TICKS: u32 = 20;
+/// How long a crash plays out before the game over screen.
+const DEATH_ANIMATION_MS: u128 = 1000;
+/// How long the crashed snake spends red, then normal, while it flashes.
+const DEATH_FLASH_MS: u128 = 100;
 
-#[derive(PartialEq, Eq, Clone)]
-enum Direction {
-    Up,
-    Left,
-    Down,
-    Right,
-}
-#[derive(PartialEq, Eq)]
-struct Position {
-    x: i32,
-    y: i32,
+/// Size of the playing field in cells, chosen at startup.
+#[derive(Clone, Copy)]
+struct GridConfig {
+    width: i32,
+    height: i32,
 }
 
-enum SwapScene {
-    _StartMenu,
-    Game,
-    GameOver,
+/// Startup options from `snek.toml` and the command line, e.g.
+/// `snek --grid 30x30 --window 1200x1200`.
+struct Config {
+    grid: GridConfig,
+    screen_width: u16,
+    screen_height: u16,
+    fullscreen: bool,
+    checkerboard: bool,
+    theme: Theme,
+    reduce_motion: bool,
+    /// Defaults for the options that can also be changed in settings.
+    tick_speed_ms: u128,
+    wrap: bool,
+    mouse_steering: bool,
+    input_map: InputMap,
+    obstacles: bool,
+    obstacle_count: usize,
+    portal_count: usize,
+    shrinking: bool,
+    mode: GameMode,
+    fruit_count: usize,
+    /// Name of the bundled level to start on.
+    level: Option<String>,
+    poison_fruit_every: u32,
+    /// Index into `RUN_KINDS` of the run quick play starts.
+    last_run: usize,
 }
 
-struct Button {
-    pos: Position,
-    width: i32,
-    height: i32,
-    label: String,
-    on_click: fn() -> Option<SwapScene>,
-}
+impl Config {
+    /// Builds the config from `snek.toml`, then `SNEK_GRID`, then the
+    /// command line, each overriding the one before. Both `get_conf` and
+    /// `main` need this, so it is only worked out the first time.
+    fn load() -> &'static Self {
+        static CONFIG: OnceLock<Config> = OnceLock::new();
+        CONFIG.get_or_init(|| {
+            let mut config = Self::from_prefs(&UserPrefs::load());
+            if let Some(grid) = GridConfig::from_env() {
+                config.grid = grid;
+            }
+            config.apply_args();
+            config
+        })
+    }
 
-impl Button {
-    #[allow(clippy::cast_possible_truncation)]
-    fn is_mouse_over_button(&self) -> bool {
-        let (mx, my) = mouse_position();
+    fn from_prefs(prefs: &UserPrefs) -> Self {
+        let tick_speed_ms =
+            prefs.tick_speed_ms.map_or(BASE_TICK_MS, u128::from);
+        let mut grid = GridConfig::default();
+        if let (Some(width), Some(height)) =
+            (prefs.grid_width, prefs.grid_height)
+        {
+            match GridConfig::new(width, height) {
+                Some(prefs_grid) => grid = prefs_grid,
+                None => eprintln!("Ignoring invalid grid {width}x{height}"),
+            }
+        }
+
+        Self {
+            grid,
+            screen_width: DEFAULT_SCREEN_WIDTH,
+            screen_height: DEFAULT_SCREEN_HEIGHT,
+            fullscreen: prefs.fullscreen.unwrap_or(false),
+            checkerboard: prefs.checkerboard.unwrap_or(true),
+            reduce_motion: prefs.reduce_motion.unwrap_or(false),
+            theme: prefs.theme.as_deref().map_or_else(Theme::default, |name| {
+                Theme::from_name(name).unwrap_or_else(|| {
+                    eprintln!("Ignoring unknown theme {name:?}");
+                    Theme::default()
+                })
+            }),
+            tick_speed_ms,
+            wrap: prefs.wrap_mode.unwrap_or(false),
+            mouse_steering: prefs.mouse_steering.unwrap_or(false),
+            input_map: InputMap::from_prefs(prefs),
+            obstacles: prefs.walls.unwrap_or(false),
+            // Defaults to whatever the difficulty would bring.
+            obstacle_count: prefs.obstacle_count.unwrap_or_else(|| {
+                DIFFICULTIES
+                    .iter()
+                    .find(|(_, ms, _)| *ms == tick_speed_ms)
+                    .map_or(0, |(_, _, count)| *count)
+            }),
+            portal_count: prefs.portal_count.unwrap_or(0),
+            shrinking: prefs.shrinking_grid.unwrap_or(false),
+            mode: prefs.mode.as_deref().map_or(GameMode::Classic, |name| {
+                GameMode::from_name(name).unwrap_or_else(|| {
+                    eprintln!("Ignoring unknown mode {name:?}");
+                    GameMode::Classic
+                })
+            }),
+            fruit_count: prefs
+                .fruit_count
+                .map_or(1, |count| count.clamp(1, MAX_FRUIT_COUNT)),
+            level: prefs.level.clone(),
+            poison_fruit_every: prefs
+                .poison_fruit_every
+                .unwrap_or(DEFAULT_POISON_FRUIT_EVERY),
+            last_run: prefs.last_run.as_deref().map_or(0, |name| {
+                RUN_KINDS
+                    .iter()
+                    .position(|(kind, _, _)| *kind == name)
+                    .unwrap_or(0)
+            }),
+        }
+    }
+
+    /// Reads `--grid WIDTHxHEIGHT` and `--window WIDTHxHEIGHT`, reporting
+    /// and skipping anything invalid.
+    fn apply_args(&mut self) {
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--grid" => {
+                    let size = args.next().unwrap_or_default();
+                    match GridConfig::parse(&size) {
+                        Some(grid) => self.grid = grid,
+                        None => {
+                            eprintln!("Ignoring invalid grid size {size:?}")
+                        }
+                    }
+                }
+                "--window" => {
+                    let size = args.next().unwrap_or_default();
+                    match parse_size::<u16>(&size)
+                        .filter(|&(width, height)| width > 0 && height > 0)
+                    {
+                        Some((width, height)) => {
+                            self.screen_width = width;
+                            self.screen_height = height;
+                        }
+                        None => {
+                            eprintln!("Ignoring invalid window size {size:?}");
+                        }
+                    }
+                }
+                _ => eprintln!("Ignoring unknown argument {arg:?}"),
+            }
+        }
+    }
 
-        (mx as i32) > self.pos.x
-            && (mx as i32) < self.pos.x + self.width
-            && (my as i32) > self.pos.y
-            && (my as i32) < self.pos.y + self.height
+    /// The options a run starts with until they're changed in settings.
+    /// The level is left for the caller to look up, since only it has the
+    /// levels loaded.
+    fn game_config(&self) -> GameConfig {
+        GameConfig {
+            grid: self.grid,
+            wrap: self.wrap,
+            mouse_steering: self.mouse_steering,
+            input_map: self.input_map.clone(),
+            tick_speed_ms: self.tick_speed_ms,
+            obstacles: self.obstacles,
+            obstacle_count: self.obstacle_count,
+            portal_count: self.portal_count,
+            shrinking: self.shrinking,
+            mode: self.mode,
+            fruit_count: self.fruit_count,
+            poison_fruit_every: self.poison_fruit_every,
+            ..GameConfig::default()
+        }
     }
 }
 
-struct GameOver {
-    restart_button: Button,
-    exit_button: Button,
+/// The entry after `current` in `all`, wrapping back round to the first.
+/// Anything not in `all` is followed by the first entry.
+fn cycle<T: Clone + PartialEq>(all: &[T], current: &T) -> T {
+    let index = all
+        .iter()
+        .position(|entry| entry == current)
+        .map_or(0, |i| (i + 1) % all.len());
+
+    all[index].clone()
 }
 
-impl GameOver {
-    fn new() -> Self {
-        let restart_button = Button {
-            pos: Position { x: 250, y: 100 },
-            width: 300,
-            height: 100,
-            label: "Restart Game".to_owned(),
-            on_click: || Some(SwapScene::Game),
-        };
+/// Splits a `WIDTHxHEIGHT` pair like `40x20`.
+fn parse_size<T: std::str::FromStr>(size: &str) -> Option<(T, T)> {
+    let (width, height) = size.split_once('x')?;
+    Some((width.trim().parse().ok()?, height.trim().parse().ok()?))
+}
 
-        let exit_button = Button {
-            pos: Position { x: 250, y: 300 },
-            width: 300,
-            height: 100,
-            label: "Exit Game".to_owned(),
-            on_click: || (exit(0)),
-        };
+impl GridConfig {
+    /// Reads the grid size from `SNEK_GRID` as `WIDTHxHEIGHT` (e.g.
+    /// `40x20`), if it is set and valid.
+    fn from_env() -> Option<Self> {
+        std::env::var("SNEK_GRID")
+            .ok()
+            .and_then(|size| Self::parse(&size))
+    }
+
+    fn parse(size: &str) -> Option<Self> {
+        let (width, height) = parse_size(size)?;
+        Self::new(width, height)
+    }
+
+    fn new(width: i32, height: i32) -> Option<Self> {
+        let sides = 2..=MAX_GRID_SIZE;
+        (sides.contains(&width) && sides.contains(&height))
+            .then_some(Self { width, height })
+    }
+}
+
+impl Default for GridConfig {
+    fn default() -> Self {
         Self {
-            restart_button,
-            exit_button,
+            width: DEFAULT_GRID_WIDTH,
+            height: DEFAULT_GRID_HEIGHT,
         }
     }
 }
 
-impl Scene for GameOver {
-    fn update(&mut self) -> Option<SwapScene> {
-        let active_button: Option<&Button> =
-            if self.restart_button.is_mouse_over_button() {
-                Some(&self.restart_button)
-            } else if self.exit_button.is_mouse_over_button() {
-                Some(&self.exit_button)
-            } else {
-                None
-            };
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize, Serialize)]
+enum Direction {
+    Up,
+    Left,
+    Down,
+    Right,
+}
 
-        if is_mouse_button_down(macroquad::prelude::MouseButton::Left) {
-            active_button.and_then(|b| (b.on_click)())
-        } else {
-            None
+impl Direction {
+    const ALL: [Self; 4] = [Self::Up, Self::Left, Self::Down, Self::Right];
+
+    fn opposite(&self) -> Self {
+        match self {
+            Self::Up => Self::Down,
+            Self::Left => Self::Right,
+            Self::Down => Self::Up,
+            Self::Right => Self::Left,
         }
     }
 
-    fn draw(&self, _renderer: &Renderer) {
-        Renderer::draw_button(&self.restart_button);
-        Renderer::draw_button(&self.exit_button);
+    /// The direction of a single step by `(dx, dy)`, or `None` for anything
+    /// that isn't one cell up, down, left or right.
+    fn from_delta(dx: i32, dy: i32) -> Option<Self> {
+        match (dx, dy) {
+            (0, -1) => Some(Self::Up),
+            (-1, 0) => Some(Self::Left),
+            (0, 1) => Some(Self::Down),
+            (1, 0) => Some(Self::Right),
+            _ => None,
+        }
     }
+}
 
-    fn reset(&mut self) {}
+impl TryFrom<(i32, i32)> for Direction {
+    type Error = ();
+
+    fn try_from((dx, dy): (i32, i32)) -> Result<Self, ()> {
+        Self::from_delta(dx, dy).ok_or(())
+    }
 }
 
-struct Menu {
-    start_button: Button,
-    exit_button: Button,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+struct Position {
+    x: i32,
+    y: i32,
 }
 
-impl Menu {
-    fn new() -> Self {
-        let start_button = Button {
-            pos: Position { x: 250, y: 100 },
-            width: 300,
-            height: 100,
-            label: "Start Game".to_owned(),
-            on_click: || Some(SwapScene::Game),
-        };
+impl Add for Position {
+    type Output = Self;
 
-        let exit_button = Button {
-            pos: Position { x: 250, y: 300 },
-            width: 300,
-            height: 100,
-            label: "Exit Game".to_owned(),
-            on_click: || exit(0),
-        };
+    fn add(self, other: Self) -> Self {
         Self {
-            start_button,
-            exit_button,
+            x: self.x + other.x,
+            y: self.y + other.y,
         }
     }
 }
 
-impl Scene for Menu {
-    fn update(&mut self) -> Option<SwapScene> {
-        let mut active_button: Option<&Button> = None;
+impl Sub for Position {
+    type Output = Self;
 
-        if self.start_button.is_mouse_over_button() {
-            active_button = Some(&self.start_button);
-        } else if self.exit_button.is_mouse_over_button() {
-            active_button = Some(&self.exit_button);
+    fn sub(self, other: Self) -> Self {
+        Self {
+            x: self.x - other.x,
+            y: self.y - other.y,
         }
+    }
+}
 
-        if is_mouse_button_down(macroquad::prelude::MouseButton::Left) {
-            active_button.and_then(|b| (b.on_click)())
-        } else {
-            None
-        }
+impl Position {
+    /// Steps between the two cells, going round corners rather than
+    /// diagonally.
+    fn manhattan_distance(&self, other: &Self) -> i32 {
+        let delta = *self - *other;
+        delta.x.abs() + delta.y.abs()
     }
 
-    fn draw(&self, _renderer: &Renderer) {
-        Renderer::draw_button(&self.start_button);
-        Renderer::draw_button(&self.exit_button);
+    /// The cell next to this one in `direction`.
+    fn moved(&self, direction: &Direction) -> Self {
+        let (x, y) = match direction {
+            Direction::Up => (0, -1),
+            Direction::Left => (-1, 0),
+            Direction::Down => (0, 1),
+            Direction::Right => (1, 0),
+        };
+        *self + Self { x, y }
+    }
+
+    /// The cells next to this one, in the order of `Direction::ALL`, with
+    /// `None` for any that are off the grid.
+    fn neighbors(
+        &self,
+        grid_width: i32,
+        grid_height: i32,
+    ) -> [Option<Self>; 4] {
+        Direction::ALL.map(|direction| {
+            Some(self.moved(&direction)).filter(|cell| {
+                (0..grid_width).contains(&cell.x)
+                    && (0..grid_height).contains(&cell.y)
+            })
+        })
     }
-    fn reset(&mut self) {}
 }
 
-struct GameScene {
-    direction: Direction,
-    bodyparts: VecDeque<Position>,
-    last_tick: Instant,
-    head_position: Position,
-    fruit_location: Position,
-    next_direction: Direction,
+/// Which keys steer the snake in each direction.
+#[derive(Clone, PartialEq)]
+struct InputMap {
+    name: &'static str,
+    bindings: [(Direction, &'static [KeyCode]); 4],
 }
-impl Scene for GameScene {
-    fn update(&mut self) -> Option<SwapScene> {
-        self.handle_input();
-        
-        if self.last_tick.elapsed().as_millis() >= TICK_SPEED_MS {
 
-            self.direction = self.next_direction.clone(); 
+impl InputMap {
+    const PRESETS: [Self; 4] = [
+        Self {
+            name: "Both",
+            bindings: [
+                (Direction::Up, &[KeyCode::W, KeyCode::Up]),
+                (Direction::Left, &[KeyCode::A, KeyCode::Left]),
+                (Direction::Down, &[KeyCode::S, KeyCode::Down]),
+                (Direction::Right, &[KeyCode::D, KeyCode::Right]),
+            ],
+        },
+        Self {
+            name: "WASD",
+            bindings: [
+                (Direction::Up, &[KeyCode::W]),
+                (Direction::Left, &[KeyCode::A]),
+                (Direction::Down, &[KeyCode::S]),
+                (Direction::Right, &[KeyCode::D]),
+            ],
+        },
+        Self {
+            name: "Arrows",
+            bindings: [
+                (Direction::Up, &[KeyCode::Up]),
+                (Direction::Left, &[KeyCode::Left]),
+                (Direction::Down, &[KeyCode::Down]),
+                (Direction::Right, &[KeyCode::Right]),
+            ],
+        },
+        Self {
+            name: "Vim",
+            bindings: [
+                (Direction::Up, &[KeyCode::K]),
+                (Direction::Left, &[KeyCode::H]),
+                (Direction::Down, &[KeyCode::J]),
+                (Direction::Right, &[KeyCode::L]),
+            ],
+        },
+    ];
 
-            match self.direction {
-                Direction::Up => self.head_position.y -= 1,
-                Direction::Left => self.head_position.x -= 1,
-                Direction::Down => self.head_position.y += 1,
-                Direction::Right => self.head_position.x += 1,
-            }
+    /// Keys remapped by hand lead back to the first preset.
+    fn next_preset(&self) -> Self {
+        cycle(&Self::PRESETS, self)
+    }
 
-            if self.head_position.x < 0
-                || self.head_position.x >= GRID_WIDTH
-                || self.head_position.y < 0
-                || self.head_position.y >= GRID_HEIGHT
-            {
-                return Some(SwapScene::GameOver);
-            }
+    fn binds(&self, key: KeyCode) -> bool {
+        self.bindings.iter().any(|(_, keys)| keys.contains(&key))
+    }
 
-            if self.head_position == self.fruit_location {
-                self.fruit_location = Self::new_fruit();
-                while self.bodyparts.contains(&self.fruit_location) {
-                    self.fruit_location = Self::new_fruit();
-                }
-            } else {
-                self.bodyparts.pop_front();
-            }
+    /// Directions whose keys went down this frame, in binding order.
+    fn pressed(&self) -> impl Iterator<Item = Direction> + '_ {
+        self.bindings
+            .iter()
+            .filter(|(_, keys)| keys.iter().any(|&key| is_key_pressed(key)))
+            .map(|(direction, _)| direction.clone())
+    }
+}
 
-            for bp in &self.bodyparts {
-                if &self.head_position == bp {
-                    return Some(SwapScene::GameOver);
-                }
-            }
-            self.bodyparts.push_back(Position {
-                x: self.head_position.x,
-                y: self.head_position.y,
-            });
+impl InputMap {
+    /// Keys that can be named in `snek.toml`.
+    const NAMED_KEYS: [KeyCode; 30] = [
+        KeyCode::A,
+        KeyCode::B,
+        KeyCode::C,
+        KeyCode::D,
+        KeyCode::E,
+        KeyCode::F,
+        KeyCode::G,
+        KeyCode::H,
+        KeyCode::I,
+        KeyCode::J,
+        KeyCode::K,
+        KeyCode::L,
+        KeyCode::M,
+        KeyCode::N,
+        KeyCode::O,
+        KeyCode::P,
+        KeyCode::Q,
+        KeyCode::R,
+        KeyCode::S,
+        KeyCode::T,
+        KeyCode::U,
+        KeyCode::V,
+        KeyCode::W,
+        KeyCode::X,
+        KeyCode::Y,
+        KeyCode::Z,
+        KeyCode::Up,
+        KeyCode::Down,
+        KeyCode::Left,
+        KeyCode::Right,
+    ];
+
+    /// The preset named in the preferences, or the default one, with any
+    /// keys remapped there swapped in.
+    fn from_prefs(prefs: &UserPrefs) -> Self {
+        let mut input_map = prefs
+            .controls
+            .as_ref()
+            .and_then(|name| {
+                Self::PRESETS
+                    .iter()
+                    .find(|preset| preset.name.eq_ignore_ascii_case(name))
+            })
+            .cloned()
+            .unwrap_or_default();
 
+        for (direction, name) in [
+            (Direction::Up, &prefs.key_up),
+            (Direction::Left, &prefs.key_left),
+            (Direction::Down, &prefs.key_down),
+            (Direction::Right, &prefs.key_right),
+        ] {
+            let Some(name) = name else {
+                continue;
+            };
+            let Some(key) = Self::NAMED_KEYS
+                .into_iter()
+                .find(|key| format!("{key:?}").eq_ignore_ascii_case(name))
+            else {
+                eprintln!("Ignoring unknown key {name:?}");
+                continue;
+            };
 
-            self.last_tick = Instant::now();
+            // Bindings are static so the presets can be consts. This only
+            // happens once at startup, so leaking the few remapped keys is
+            // fine.
+            if let Some((_, keys)) = input_map
+                .bindings
+                .iter_mut()
+                .find(|(bound, _)| *bound == direction)
+            {
+                *keys = Box::leak(Box::new([key]));
+            }
+            input_map.name = "Custom";
         }
-        None
+        input_map
+    }
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        Self::PRESETS[0].clone()
     }
+}
+
+/// Options picked on the menu that change how a run plays.
+#[derive(Clone)]
+struct GameConfig {
+    grid: GridConfig,
+    wrap: bool,
+    /// Holding the left mouse button steers player one towards it.
+    mouse_steering: bool,
+    input_map: InputMap,
+    /// Tick interval at the start of a run, before any speed-up.
+    tick_speed_ms: u128,
+    initial_length: usize,
+    /// Put walls inside the arena as well as around it.
+    obstacles: bool,
+    /// Walls scattered at random on top of the `obstacles` layout, and
+    /// only with it.
+    obstacle_count: usize,
+    /// Pairs of portals scattered at random.
+    portal_count: usize,
+    /// Shrink the play area as the run goes on.
+    shrinking: bool,
+    mode: GameMode,
+    /// A fixed layout to play on, which takes the place of `obstacles`.
+    level: Option<Rc<Level>>,
+    /// How many fruit are out at once.
+    fruit_count: usize,
+    /// Fruit eaten between poison fruit, where 0 means there is none.
+    poison_fruit_every: u32,
+    /// A second snake on the arrow keys, with player one moved to WASD.
+    two_player: bool,
+    /// A snake steered by the computer racing the player to the fruit.
+    vs_computer: bool,
+}
 
-    fn draw(&self, renderer: &Renderer) {
-        renderer.draw_head(&self.head_position);
-        for bp in self.bodyparts.range(..self.bodyparts.len()-1) {
-            renderer.draw_bodypart(bp);
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            grid: GridConfig::default(),
+            wrap: false,
+            mouse_steering: false,
+            input_map: InputMap::default(),
+            tick_speed_ms: BASE_TICK_MS,
+            initial_length: 1,
+            obstacles: false,
+            obstacle_count: 0,
+            portal_count: 0,
+            shrinking: false,
+            mode: GameMode::Classic,
+            level: None,
+            fruit_count: 1,
+            poison_fruit_every: DEFAULT_POISON_FRUIT_EVERY,
+            two_player: false,
+            vs_computer: false,
         }
+    }
+}
 
-        renderer.draw_fruit(&self.fruit_location);
+impl GameConfig {
+    /// The difficulty preset with this tick speed, or `"Custom"`.
+    fn difficulty_name(&self) -> &'static str {
+        DIFFICULTIES
+            .iter()
+            .find(|(_, ms, _)| *ms == self.tick_speed_ms)
+            .map_or("Custom", |(name, _, _)| name)
     }
-    fn reset(&mut self) {
-        let head_x = GRID_WIDTH / 2;
-        let head_y = GRID_HEIGHT / 2;
+}
 
-        self.bodyparts = VecDeque::new();
-        self.bodyparts.push_back(Position {
-            x: head_x,
-            y: head_y,
-        });
+/// What it takes to keep a run going, besides not crashing.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum GameMode {
+    Classic,
+    /// The run ends once this much play time is up.
+    Timed {
+        seconds: u32,
+    },
+    /// At least `fruits_needed` fruit have to be eaten every `window_ticks`
+    /// ticks.
+    Quota {
+        fruits_needed: u32,
+        window_ticks: u32,
+    },
+}
 
-        self.head_position = Position {
-            x: head_x,
-            y: head_y,
-        };
+impl GameMode {
+    const ALL: [Self; 3] = [
+        Self::Classic,
+        Self::Timed { seconds: 120 },
+        Self::Quota {
+            fruits_needed: 3,
+            window_ticks: 100,
+        },
+    ];
 
-        self.fruit_location = Self::new_fruit();
-        while self.bodyparts.contains(&self.fruit_location) {
-            self.fruit_location = Self::new_fruit();
+    fn name(self) -> &'static str {
+        match self {
+            Self::Classic => "Classic",
+            Self::Timed { .. } => "Timed",
+            Self::Quota { .. } => "Quota",
         }
-        self.direction = Direction::Up;
-        self.next_direction = Direction::Up;
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(name))
+    }
+
+    fn next(self) -> Self {
+        cycle(&Self::ALL, &self)
     }
 }
 
-impl GameScene {
-    fn new() -> Self {
-        let head_x = GRID_WIDTH / 2;
-        let head_y = GRID_HEIGHT / 2;
+/// How a finished run went, handed from the game to the game-over screen.
+#[derive(Clone, Default)]
+struct RunResults {
+    score: u32,
+    length: usize,
+    fruits_eaten: u32,
+    /// Time spent playing, not counting pauses.
+    duration: Duration,
+    /// The snake filled the whole grid.
+    won: bool,
+    /// Who outlasted the other in a two-player run, whose score this is.
+    winner: Option<Player>,
+}
 
-        let mut bodyparts = VecDeque::new();
-        bodyparts.push_back(Position {
-            x: head_x,
-            y: head_y,
-        });
+enum SwapScene {
+    StartMenu,
+    Settings,
+    HowToPlay,
+    Game(GameConfig),
+    GameOver(RunResults),
+}
 
-        let head_pos = Position {
-            x: head_x,
-            y: head_y,
-        };
+/// A clickable button. `S` is the scene that owns it, which `on_click`
+/// gets access to.
+struct Button<S> {
+    pos: Position,
+    width: i32,
+    height: i32,
+    label: String,
+    shortcut: Option<KeyCode>,
+    /// Set while a click that started on this button is still held.
+    armed: bool,
+    on_click: fn(&mut S) -> Option<SwapScene>,
+}
 
-        let mut fruit_location = Self::new_fruit();
-        while bodyparts.contains(&fruit_location) {
-            fruit_location = Self::new_fruit();
-        }
+impl<S> Button<S> {
+    /// Where the button is on screen right now. Drawing and hit-testing
+    /// both go through this so they always agree.
+    #[allow(clippy::cast_precision_loss)]
+    fn rect(&self) -> Rect {
+        let layout = MenuLayout::current();
+        Rect::new(
+            layout.x(self.pos.x as f32),
+            layout.y(self.pos.y as f32),
+            layout.size(self.width as f32),
+            layout.size(self.height as f32),
+        )
+    }
 
-        Self {
-            direction: Direction::Up,
-            bodyparts,
-            last_tick: Instant::now(),
-            head_position: head_pos,
-            fruit_location,
-            next_direction: Direction::Up, 
-        }
+    fn is_mouse_over_button(&self) -> bool {
+        self.rect().contains(mouse_position().into())
     }
 
-    fn new_fruit() -> Position {
-        let x = rand::thread_rng().gen_range(0..GRID_WIDTH - 1);
-        let y = rand::thread_rng().gen_range(0..GRID_HEIGHT - 1);
+    fn shortcut_label(&self) -> Option<String> {
+        self.shortcut.map(|key| match key {
+            KeyCode::Key1 => "1".to_owned(),
+            KeyCode::Key2 => "2".to_owned(),
+            KeyCode::Key3 => "3".to_owned(),
+            KeyCode::Key4 => "4".to_owned(),
+            KeyCode::Key5 => "5".to_owned(),
+            KeyCode::Key6 => "6".to_owned(),
+            KeyCode::Key7 => "7".to_owned(),
+            KeyCode::Key8 => "8".to_owned(),
+            KeyCode::Key9 => "9".to_owned(),
+            KeyCode::Key0 => "0".to_owned(),
+            KeyCode::Escape => "Esc".to_owned(),
+            key => format!("{key:?}"),
+        })
+    }
 
-        Position { x, y }
+    /// Finds the first button whose shortcut key went down this frame.
+    fn find_shortcut_pressed<'a>(buttons: &[&'a Self]) -> Option<&'a Self> {
+        buttons
+            .iter()
+            .find(|b| b.shortcut.is_some_and(is_key_pressed))
+            .copied()
     }
 
-    fn handle_input(&mut self) {
-        if is_key_down(KeyCode::W) && self.direction != Direction::Down {
-            self.next_direction = Direction::Up;
-        }
+    /// Tracks the left mouse button across `buttons` and returns the
+    /// handler of the one that was clicked this frame, if any. A button is
+    /// armed when the press starts over it and only fires if the release
+    /// happens over it too, so holding the mouse or dragging in from
+    /// elsewhere never clicks anything.
+    fn poll_click(
+        buttons: &mut [&mut Self],
+    ) -> Option<fn(&mut S) -> Option<SwapScene>> {
+        let pressed = is_mouse_button_pressed(MouseButton::Left);
+        let released = is_mouse_button_released(MouseButton::Left);
+        let mut clicked = None;
 
-        if is_key_down(KeyCode::A) && self.direction != Direction::Right {
-            self.next_direction = Direction::Left;
+        for button in buttons.iter_mut() {
+            let over = button.is_mouse_over_button();
+            if pressed {
+                button.armed = over;
+            }
+            if released {
+                if button.armed && over {
+                    clicked = Some(button.on_click);
+                }
+                button.armed = false;
+            }
         }
+        clicked
+    }
 
-        if is_key_down(KeyCode::S) && self.direction != Direction::Up {
-            self.next_direction = Direction::Down;
+    /// Moves `focused` through `buttons` with Tab/Down and Up, wrapping at
+    /// either end, and returns the handler of the focused button if Enter
+    /// or Space went down this frame.
+    fn poll_focus(
+        buttons: &[&Self],
+        focused: &mut usize,
+    ) -> Option<fn(&mut S) -> Option<SwapScene>> {
+        if is_key_pressed(KeyCode::Tab) || is_key_pressed(KeyCode::Down) {
+            *focused = (*focused + 1) % buttons.len();
+        }
+        if is_key_pressed(KeyCode::Up) {
+            *focused = (*focused + buttons.len() - 1) % buttons.len();
         }
 
-        if is_key_down(KeyCode::D) && self.direction != Direction::Left {
-            self.next_direction = Direction::Right;
+        if is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::Space) {
+            buttons.get(*focused).map(|b| b.on_click)
+        } else {
+            None
         }
     }
 }
 
-struct Game {
-    renderer: Renderer,
-    scenes: Vec<Rc<RefCell<dyn Scene>>>,
-    active_scene: Option<Rc<RefCell<dyn Scene>>>,
-}
+/// A scene with buttons. Each scene lists its buttons once, here, and that
+/// list drives the shortcuts, focus, clicks and drawing alike.
+trait ButtonScene: Sized {
+    /// Every button, in focus order.
+    fn buttons(&self) -> Vec<&Button<Self>>;
+    fn buttons_mut(&mut self) -> Vec<&mut Button<Self>>;
+    fn focused_button_index(&mut self) -> &mut usize;
 
-impl Game {
-    fn new() -> Self {
-        Self {
-            renderer: Renderer::new(),
-            scenes: Vec::new(),
-            active_scene: None,
+    /// Handles this frame's shortcut keys, focus keys and mouse clicks,
+    /// returning the handler of the button they picked, if any.
+    fn poll_buttons(&mut self) -> Option<fn(&mut Self) -> Option<SwapScene>> {
+        if let Some(button) = Button::find_shortcut_pressed(&self.buttons()) {
+            return Some(button.on_click);
         }
-    }
 
-    fn update(&mut self) {
-        let swap = self.active_scene.as_mut().map_or_else(
-            || panic!("Update called without active scene"),
-            |scene| {
-                scene.try_borrow_mut().map_or_else(
-                    |_| panic!("Fatal Error: Failed to borrow scene"),
-                    |mut scene| scene.update(),
-                )
-            },
-        );
+        let mut focused = *self.focused_button_index();
+        let on_click = Button::poll_focus(&self.buttons(), &mut focused);
+        *self.focused_button_index() = focused;
+        if on_click.is_some() {
+            return on_click;
+        }
 
-        if let Some(s) = swap {
-            match s {
-                SwapScene::_StartMenu => self.set_scene(0),
-                SwapScene::Game => {
-                    self.set_scene(1);
-                    self.active_scene.as_mut().map_or_else(
-                        || panic!("Unreachable"),
-                        |scene| {
-                            scene.try_borrow_mut().map_or_else(
-                                |_| panic!("Failed to borrow mut"),
-                                |mut scene| scene.reset(),
-                            );
-                        },
-                    );
-                }
-                SwapScene::GameOver => self.set_scene(2),
-            }
+        Button::poll_click(&mut self.buttons_mut())
+    }
+
+    fn draw_buttons(&self, renderer: &Renderer, focused: usize) {
+        for (i, button) in self.buttons().into_iter().enumerate() {
+            renderer.draw_button(button, i == focused);
         }
     }
+}
+
+struct Star {
+    /// Position as a fraction of the window, so it survives a resize.
+    x: f32,
+    y: f32,
+    near: bool,
+}
+
+/// Menus are laid out on a square of `MenuLayout::SIZE` units, which is
+/// scaled to fit the window and centred in it.
+struct MenuLayout {
+    scale: f32,
+    left: f32,
+    top: f32,
+}
+
+impl MenuLayout {
+    const SIZE: f32 = 800.;
 
-    fn draw(&self) {
-        match &self.active_scene {
-            Some(s) => s.borrow().draw(&self.renderer),
-            None => panic!("`draw` called without active scene."),
+    fn current() -> Self {
+        let scale =
+            (screen_width() / Self::SIZE).min(screen_height() / Self::SIZE);
+        Self {
+            scale,
+            left: (screen_width() - Self::SIZE * scale) / 2.,
+            top: (screen_height() - Self::SIZE * scale) / 2.,
         }
     }
 
-    fn add_scene(&mut self, scene: Rc<RefCell<dyn Scene>>) {
-        self.scenes.push(scene);
+    fn x(&self, x: f32) -> f32 {
+        self.left + self.size(x)
+    }
+
+    fn y(&self, y: f32) -> f32 {
+        self.top + self.size(y)
     }
 
-    fn set_scene(&mut self, index: usize) {
-        self.active_scene = Some(Rc::clone(&self.scenes[index]));
+    fn size(&self, size: f32) -> f32 {
+        size * self.scale
     }
 }
 
-struct Renderer {
-    cell_width: f32,
-    cell_height: f32,
-    object_width: f32,
-    object_height: f32,
-    object_gap_width: f32,
-    object_gap_height: f32,
+/// Background stars scattered over two depths. Stars only store their
+/// starting point; the drift is derived from the time when drawn.
+struct Starfield {
+    stars: Vec<Star>,
 }
 
-impl Renderer {
-    #[allow(clippy::cast_precision_loss)]
+impl Starfield {
     fn new() -> Self {
-        let cell_width = SCREEN_WIDTH / GRID_WIDTH as f32;
-        let cell_height = SCREEN_HEIGHT / GRID_HEIGHT as f32;
+        let mut rng = rand::thread_rng();
+        let stars = (0..STAR_COUNT)
+            .map(|i| Star {
+                x: rng.gen_range(0. ..1.),
+                y: rng.gen_range(0. ..1.),
+                near: i % 2 == 0,
+            })
+            .collect();
+
+        Self { stars }
+    }
+}
 
-        let object_gap_width = cell_width * 0.1;
-        let object_gap_height = cell_height * 0.1;
+struct GameOver {
+    menu_button: Button<Self>,
+    exit_button: Button<Self>,
+    focused_button_index: usize,
+    starfield: Starfield,
+    results: RunResults,
+    new_record: bool,
+}
 
-        let body_width = cell_width - object_gap_width;
-        let body_height = cell_height - object_gap_height;
+impl GameOver {
+    fn new() -> Self {
+        let menu_button: Button<Self> = Button {
+            pos: Position { x: 250, y: 350 },
+            width: 300,
+            height: 100,
+            label: "Main Menu".to_owned(),
+            shortcut: Some(KeyCode::Key1),
+            armed: false,
+            on_click: |_| Some(SwapScene::StartMenu),
+        };
 
+        let exit_button: Button<Self> = Button {
+            pos: Position { x: 250, y: 500 },
+            width: 300,
+            height: 100,
+            label: EXIT_LABEL.to_owned(),
+            shortcut: Some(KeyCode::Key2),
+            armed: false,
+            on_click: quit,
+        };
         Self {
-            cell_width,
-            cell_height,
-            object_width: body_width,
-            object_height: body_height,
-            object_gap_height,
-            object_gap_width,
+            menu_button,
+            exit_button,
+            focused_button_index: 0,
+            starfield: Starfield::new(),
+            results: RunResults::default(),
+            new_record: false,
         }
     }
+}
 
-    fn draw_bodypart(&self, bp: &Position) {
-        self.draw_rect_at_point(bp, GREEN);
+impl ButtonScene for GameOver {
+    fn buttons(&self) -> Vec<&Button<Self>> {
+        vec![&self.menu_button, &self.exit_button]
     }
 
-    fn draw_head(&self, head: &Position) {
-        self.draw_rect_at_point(head, Color { r: 0.8, g: 1., b: 0.8, a: 1.})
+    fn buttons_mut(&mut self) -> Vec<&mut Button<Self>> {
+        vec![&mut self.menu_button, &mut self.exit_button]
     }
 
-    fn draw_fruit(&self, f: &Position) {
-        self.draw_rect_at_point(f, RED);
+    fn focused_button_index(&mut self) -> &mut usize {
+        &mut self.focused_button_index
     }
+}
 
-    #[allow(clippy::cast_precision_loss)]
-    fn draw_rect_at_point(&self, p: &Position, c: Color) {
-        let real_x = p.x as f32 * self.cell_width;
-        let real_y = p.y as f32 * self.cell_height;
-
-        let real_x = real_x + self.object_gap_width / 2.;
-        let real_y = real_y + self.object_gap_height / 2.;
+impl Scene for GameOver {
+    fn update(&mut self) -> Option<SwapScene> {
+        self.poll_buttons().and_then(|on_click| on_click(self))
+    }
 
-        draw_rectangle(
-            real_x,
-            real_y,
-            self.object_width,
-            self.object_height,
-            c,
-        );
+    fn draw_background(&self, renderer: &Renderer) {
+        renderer.clear();
+        renderer.draw_starfield(&self.starfield);
     }
 
-    #[allow(clippy::cast_precision_loss)]
-    fn draw_button(but: &Button) {
-        draw_rectangle(
-            but.pos.x as f32,
-            but.pos.y as f32,
-            but.width as f32,
-            but.height as f32,
-            WHITE,
-        );
+    fn draw(&self, renderer: &Renderer, high_score: &HighScore) {
+        let results = &self.results;
+        let seconds = results.duration.as_secs();
 
-        draw_text(
-            &but.label,
-            but.pos.x as f32,
-            (but.pos.y + but.height / 2 + 12) as f32,
-            50f32,
-            GREEN,
+        renderer.draw_text_centered(
+            &format!("Score: {} / Best: {}", results.score, high_score.best()),
+            60.,
+            50,
+            renderer.theme().text,
         );
-    }
-}
+        renderer.draw_text_centered(
+            &format!("Length: {}", results.length),
+            120.,
+            40,
+            renderer.theme().text,
+        );
+        renderer.draw_text_centered(
+            &format!("Fruits eaten: {}", results.fruits_eaten),
+            170.,
+            40,
+            renderer.theme().text,
+        );
+        renderer.draw_text_centered(
+            &format!("Time: {}:{:02}", seconds / 60, seconds % 60),
+            220.,
+            40,
+            renderer.theme().text,
+        );
+        if let Some(winner) = results.winner {
+            renderer.draw_text_centered(
+                &format!("{} wins!", winner.name()),
+                260.,
+                40,
+                renderer.theme().good,
+            );
+        } else if results.won {
+            renderer.draw_text_centered(
+                "You win!",
+                260.,
+                40,
+                renderer.theme().good,
+            );
+        }
+        if self.new_record {
+            renderer.draw_text_centered(
+                "New record!",
+                300.,
+                40,
+                renderer.theme().highlight,
+            );
+        }
+        self.draw_buttons(renderer, self.focused_button_index);
+    }
+
+    fn on_enter(&mut self) {
+        self.focused_button_index = 0;
+    }
+
+    fn set_results(&mut self, results: &RunResults, new_record: bool) {
+        self.results = results.clone();
+        self.new_record = new_record;
+    }
+}
+
+struct Menu {
+    start_button: Button<Self>,
+    two_player_button: Button<Self>,
+    vs_computer_button: Button<Self>,
+    settings_button: Button<Self>,
+    exit_button: Button<Self>,
+    focused_button_index: usize,
+    starfield: Starfield,
+    config: Rc<RefCell<GameConfig>>,
+    /// Index into `RUN_KINDS` of the last run started, which quick play
+    /// starts again.
+    last_run: usize,
+}
+
+impl Menu {
+    fn new(config: Rc<RefCell<GameConfig>>, last_run: usize) -> Self {
+        let start_button: Button<Self> = Button {
+            pos: Position { x: 250, y: 100 },
+            width: 300,
+            height: 100,
+            label: "Start Game".to_owned(),
+            shortcut: Some(KeyCode::Key1),
+            armed: false,
+            on_click: |menu| Some(menu.start(0)),
+        };
+
+        let two_player_button: Button<Self> = Button {
+            pos: Position { x: 250, y: 260 },
+            width: 300,
+            height: 100,
+            label: "2 Players".to_owned(),
+            shortcut: Some(KeyCode::Key2),
+            armed: false,
+            on_click: |menu| Some(menu.start(1)),
+        };
+
+        let vs_computer_button: Button<Self> = Button {
+            pos: Position { x: 250, y: 380 },
+            width: 300,
+            height: 100,
+            label: "Vs Computer".to_owned(),
+            shortcut: Some(KeyCode::Key3),
+            armed: false,
+            on_click: |menu| Some(menu.start(2)),
+        };
+
+        let settings_button: Button<Self> = Button {
+            pos: Position { x: 250, y: 500 },
+            width: 300,
+            height: 100,
+            label: "Settings".to_owned(),
+            shortcut: Some(KeyCode::Key4),
+            armed: false,
+            on_click: |_| Some(SwapScene::Settings),
+        };
+
+        let exit_button: Button<Self> = Button {
+            pos: Position { x: 250, y: 620 },
+            width: 300,
+            height: 100,
+            label: EXIT_LABEL.to_owned(),
+            shortcut: Some(KeyCode::Key5),
+            armed: false,
+            on_click: quit,
+        };
+        Self {
+            start_button,
+            two_player_button,
+            vs_computer_button,
+            settings_button,
+            exit_button,
+            focused_button_index: 0,
+            starfield: Starfield::new(),
+            config,
+            last_run,
+        }
+    }
+
+    /// Starts a run of the `RUN_KINDS` entry at `run`, remembering it for
+    /// quick play next time.
+    fn start(&mut self, run: usize) -> SwapScene {
+        let (name, two_player, vs_computer) = RUN_KINDS[run];
+        if run != self.last_run {
+            self.last_run = run;
+            Game::save_pref(|prefs| prefs.last_run = Some(name.to_owned()));
+        }
+        SwapScene::Game(GameConfig {
+            two_player,
+            vs_computer,
+            ..self.config.borrow().clone()
+        })
+    }
+
+    fn quick_play_label(&self) -> String {
+        let config = self.config.borrow();
+        format!(
+            "Quick play (Enter): {} {}x{}, {}, {}",
+            config.mode.name(),
+            config.grid.width,
+            config.grid.height,
+            config.difficulty_name(),
+            RUN_KINDS[self.last_run].0,
+        )
+    }
+}
+
+impl ButtonScene for Menu {
+    fn buttons(&self) -> Vec<&Button<Self>> {
+        vec![
+            &self.start_button,
+            &self.two_player_button,
+            &self.vs_computer_button,
+            &self.settings_button,
+            &self.exit_button,
+        ]
+    }
+
+    fn buttons_mut(&mut self) -> Vec<&mut Button<Self>> {
+        vec![
+            &mut self.start_button,
+            &mut self.two_player_button,
+            &mut self.vs_computer_button,
+            &mut self.settings_button,
+            &mut self.exit_button,
+        ]
+    }
+
+    fn focused_button_index(&mut self) -> &mut usize {
+        &mut self.focused_button_index
+    }
+}
+
+impl Scene for Menu {
+    /// Enter starts quick play here rather than clicking the focused
+    /// button, which Space still does.
+    fn update(&mut self) -> Option<SwapScene> {
+        if is_key_pressed(KeyCode::Enter) {
+            return Some(self.start(self.last_run));
+        }
+        if is_key_pressed(KeyCode::F1) {
+            return Some(SwapScene::HowToPlay);
+        }
+        self.poll_buttons().and_then(|on_click| on_click(self))
+    }
+
+    fn draw_background(&self, renderer: &Renderer) {
+        renderer.clear();
+        renderer.draw_starfield(&self.starfield);
+    }
+
+    fn draw(&self, renderer: &Renderer, high_score: &HighScore) {
+        renderer.draw_text_centered(
+            &format!("Best: {}", high_score.best()),
+            60.,
+            50,
+            renderer.theme().text,
+        );
+        renderer.draw_text_centered(
+            &self.quick_play_label(),
+            236.,
+            20,
+            renderer.theme().text,
+        );
+        renderer.draw_text_centered(
+            "F1: How to play",
+            770.,
+            24,
+            renderer.theme().text,
+        );
+        self.draw_buttons(renderer, self.focused_button_index);
+    }
+    fn on_enter(&mut self) {
+        self.focused_button_index = 0;
+    }
+}
+
+/// The controls and rules, opened from the menu with F1.
+struct HowToPlay {
+    back_button: Button<Self>,
+    focused_button_index: usize,
+    starfield: Starfield,
+}
+
+impl HowToPlay {
+    const LINES: [&str; 9] = [
+        "Steer with WASD, the arrow keys or the keys set in Settings",
+        "Eat fruit to grow and score, and don't hit a wall or a snake",
+        "Golden fruit is worth more, and poison fruit shrinks you",
+        "Speed boosts double your speed for a while",
+        "Space, P or Esc pauses, and Tab shows the way to the fruit",
+        "With mouse steering on, hold the left button to steer",
+        "F5 saves a solo run and F9 loads it",
+        "F2 switches the checkerboard and F11 fullscreen",
+        "On the menu, 1 to 5 pick a button and Enter plays again",
+    ];
+
+    fn new() -> Self {
+        let back_button: Button<Self> = Button {
+            pos: Position { x: 250, y: 620 },
+            width: 300,
+            height: 100,
+            label: "Back".to_owned(),
+            shortcut: Some(KeyCode::Escape),
+            armed: false,
+            on_click: |_| Some(SwapScene::StartMenu),
+        };
+        Self {
+            back_button,
+            focused_button_index: 0,
+            starfield: Starfield::new(),
+        }
+    }
+}
+
+impl ButtonScene for HowToPlay {
+    fn buttons(&self) -> Vec<&Button<Self>> {
+        vec![&self.back_button]
+    }
+
+    fn buttons_mut(&mut self) -> Vec<&mut Button<Self>> {
+        vec![&mut self.back_button]
+    }
+
+    fn focused_button_index(&mut self) -> &mut usize {
+        &mut self.focused_button_index
+    }
+}
+
+impl Scene for HowToPlay {
+    fn update(&mut self) -> Option<SwapScene> {
+        if is_key_pressed(KeyCode::F1) {
+            return Some(SwapScene::StartMenu);
+        }
+        self.poll_buttons().and_then(|on_click| on_click(self))
+    }
+
+    fn draw_background(&self, renderer: &Renderer) {
+        renderer.clear();
+        renderer.draw_starfield(&self.starfield);
+    }
+
+    fn draw(&self, renderer: &Renderer, _high_score: &HighScore) {
+        renderer.draw_text_centered(
+            "How to play",
+            80.,
+            50,
+            renderer.theme().text,
+        );
+        for (y, line) in (0_u16..)
+            .map(|i| 150. + 50. * f32::from(i))
+            .zip(Self::LINES)
+        {
+            renderer.draw_text_centered(line, y, 24, renderer.theme().text);
+        }
+        self.draw_buttons(renderer, self.focused_button_index);
+    }
+
+    fn on_enter(&mut self) {
+        self.focused_button_index = 0;
+    }
+}
+
+/// Who a run is played with, as a name and the `two_player` and
+/// `vs_computer` settings it starts with.
+const RUN_KINDS: [(&str, bool, bool); 3] = [
+    ("Solo", false, false),
+    ("2 Players", true, false),
+    ("Vs Computer", false, true),
+];
+
+/// Difficulty presets, as a name, the tick length a run starts at and how
+/// many walls are scattered over the arena when walls are on.
+const DIFFICULTIES: [(&str, u128, usize); 3] = [
+    ("Easy", 350, 0),
+    ("Medium", BASE_TICK_MS, 5),
+    ("Hard", 150, 10),
+];
+
+/// Options for the next run. The config is shared with the menu, so
+/// changes here apply to the next game started from there.
+struct Settings {
+    easy_button: Button<Self>,
+    medium_button: Button<Self>,
+    hard_button: Button<Self>,
+    wrap_button: Button<Self>,
+    obstacles_button: Button<Self>,
+    keys_button: Button<Self>,
+    level_button: Button<Self>,
+    fruit_button: Button<Self>,
+    grid_button: Button<Self>,
+    mode_button: Button<Self>,
+    theme_button: Button<Self>,
+    mouse_button: Button<Self>,
+    back_button: Button<Self>,
+    focused_button_index: usize,
+    starfield: Starfield,
+    config: Rc<RefCell<GameConfig>>,
+    /// Shared with the renderer, so a new theme shows straight away.
+    theme: Rc<Cell<Theme>>,
+    /// The bundled levels that fit the chosen grid.
+    levels: Vec<Rc<Level>>,
+    /// Whether the grid was picked here. Until it is, the grid may have
+    /// come from `--grid` or `SNEK_GRID` and isn't saved.
+    grid_changed: bool,
+}
+
+impl Settings {
+    fn new(
+        config: Rc<RefCell<GameConfig>>,
+        levels: Vec<Rc<Level>>,
+        theme: Rc<Cell<Theme>>,
+    ) -> Self {
+        let easy_button: Button<Self> = Button {
+            pos: Position { x: 75, y: 90 },
+            width: 200,
+            height: 90,
+            label: DIFFICULTIES[0].0.to_owned(),
+            shortcut: Some(KeyCode::Key1),
+            armed: false,
+            on_click: |settings| settings.set_difficulty(0),
+        };
+
+        let medium_button: Button<Self> = Button {
+            pos: Position { x: 300, y: 90 },
+            width: 200,
+            height: 90,
+            label: DIFFICULTIES[1].0.to_owned(),
+            shortcut: Some(KeyCode::Key2),
+            armed: false,
+            on_click: |settings| settings.set_difficulty(1),
+        };
+
+        let hard_button: Button<Self> = Button {
+            pos: Position { x: 525, y: 90 },
+            width: 200,
+            height: 90,
+            label: DIFFICULTIES[2].0.to_owned(),
+            shortcut: Some(KeyCode::Key3),
+            armed: false,
+            on_click: |settings| settings.set_difficulty(2),
+        };
+
+        let wrap_button: Button<Self> = Button {
+            pos: Position { x: 75, y: 200 },
+            width: 300,
+            height: 90,
+            label: Self::wrap_label(config.borrow().wrap),
+            shortcut: Some(KeyCode::Key4),
+            armed: false,
+            on_click: |settings| {
+                let wrap = {
+                    let mut config = settings.config.borrow_mut();
+                    config.wrap = !config.wrap;
+                    config.wrap
+                };
+                settings.wrap_button.label = Self::wrap_label(wrap);
+                None
+            },
+        };
+
+        let obstacles_button: Button<Self> = Button {
+            pos: Position { x: 425, y: 200 },
+            width: 300,
+            height: 90,
+            label: Self::obstacles_label(config.borrow().obstacles),
+            shortcut: Some(KeyCode::Key5),
+            armed: false,
+            on_click: |settings| {
+                let obstacles = {
+                    let mut config = settings.config.borrow_mut();
+                    config.obstacles = !config.obstacles;
+                    config.obstacles
+                };
+                settings.obstacles_button.label =
+                    Self::obstacles_label(obstacles);
+                None
+            },
+        };
+
+        let keys_button: Button<Self> = Button {
+            pos: Position { x: 75, y: 310 },
+            width: 300,
+            height: 90,
+            label: Self::keys_label(&config.borrow().input_map),
+            shortcut: Some(KeyCode::Key6),
+            armed: false,
+            on_click: |settings| {
+                let label = {
+                    let mut config = settings.config.borrow_mut();
+                    config.input_map = config.input_map.next_preset();
+                    Self::keys_label(&config.input_map)
+                };
+                settings.keys_button.label = label;
+                None
+            },
+        };
+
+        let level_button: Button<Self> = Button {
+            pos: Position { x: 425, y: 310 },
+            width: 300,
+            height: 90,
+            label: Self::level_label(config.borrow().level.as_deref()),
+            shortcut: Some(KeyCode::Key7),
+            armed: false,
+            on_click: |settings| {
+                settings.next_level();
+                None
+            },
+        };
+
+        let fruit_button: Button<Self> = Button {
+            pos: Position { x: 75, y: 420 },
+            width: 300,
+            height: 90,
+            label: Self::fruit_label(config.borrow().fruit_count),
+            shortcut: Some(KeyCode::Key8),
+            armed: false,
+            on_click: |settings| {
+                let fruit_count = {
+                    let mut config = settings.config.borrow_mut();
+                    config.fruit_count =
+                        config.fruit_count % MAX_FRUIT_COUNT + 1;
+                    config.fruit_count
+                };
+                settings.fruit_button.label = Self::fruit_label(fruit_count);
+                None
+            },
+        };
+
+        let grid_button: Button<Self> = Button {
+            pos: Position { x: 425, y: 420 },
+            width: 300,
+            height: 90,
+            label: Self::grid_label(&config.borrow().grid),
+            shortcut: Some(KeyCode::Key9),
+            armed: false,
+            on_click: |settings| {
+                settings.next_grid();
+                None
+            },
+        };
+
+        let mode_button: Button<Self> = Button {
+            pos: Position { x: 75, y: 530 },
+            width: 300,
+            height: 90,
+            label: Self::mode_label(config.borrow().mode),
+            shortcut: Some(KeyCode::Key0),
+            armed: false,
+            on_click: |settings| {
+                let mode = {
+                    let mut config = settings.config.borrow_mut();
+                    config.mode = config.mode.next();
+                    config.mode
+                };
+                settings.mode_button.label = Self::mode_label(mode);
+                None
+            },
+        };
+
+        let theme_button: Button<Self> = Button {
+            pos: Position { x: 425, y: 530 },
+            width: 300,
+            height: 90,
+            label: Self::theme_label(&theme.get()),
+            shortcut: Some(KeyCode::T),
+            armed: false,
+            on_click: |settings| {
+                let theme = settings.theme.get().next();
+                settings.theme.set(theme);
+                settings.theme_button.label = Self::theme_label(&theme);
+                None
+            },
+        };
+
+        let mouse_button: Button<Self> = Button {
+            pos: Position { x: 75, y: 640 },
+            width: 300,
+            height: 90,
+            label: Self::mouse_label(config.borrow().mouse_steering),
+            shortcut: Some(KeyCode::M),
+            armed: false,
+            on_click: |settings| {
+                let mouse_steering = {
+                    let mut config = settings.config.borrow_mut();
+                    config.mouse_steering = !config.mouse_steering;
+                    config.mouse_steering
+                };
+                settings.mouse_button.label = Self::mouse_label(mouse_steering);
+                None
+            },
+        };
+
+        let back_button: Button<Self> = Button {
+            pos: Position { x: 425, y: 640 },
+            width: 300,
+            height: 90,
+            label: "Back".to_owned(),
+            shortcut: Some(KeyCode::Escape),
+            armed: false,
+            on_click: |_| Some(SwapScene::StartMenu),
+        };
+        Self {
+            easy_button,
+            medium_button,
+            hard_button,
+            wrap_button,
+            obstacles_button,
+            keys_button,
+            level_button,
+            fruit_button,
+            grid_button,
+            mode_button,
+            theme_button,
+            mouse_button,
+            back_button,
+            focused_button_index: 0,
+            starfield: Starfield::new(),
+            config,
+            levels,
+            theme,
+            grid_changed: false,
+        }
+    }
+
+    fn set_difficulty(&mut self, index: usize) -> Option<SwapScene> {
+        let (_, tick_speed_ms, obstacle_count) = DIFFICULTIES[index];
+        let mut config = self.config.borrow_mut();
+        config.tick_speed_ms = tick_speed_ms;
+        config.obstacle_count = obstacle_count;
+        None
+    }
+
+    fn wrap_label(wrap: bool) -> String {
+        format!("Wrap: {}", if wrap { "On" } else { "Off" })
+    }
+
+    fn mouse_label(mouse_steering: bool) -> String {
+        format!("Mouse: {}", if mouse_steering { "On" } else { "Off" })
+    }
+
+    fn obstacles_label(obstacles: bool) -> String {
+        format!("Walls: {}", if obstacles { "On" } else { "Off" })
+    }
+
+    fn keys_label(input_map: &InputMap) -> String {
+        format!("Keys: {}", input_map.name)
+    }
+
+    /// Cycles from the open arena through each level and back again.
+    fn next_level(&mut self) {
+        let mut config = self.config.borrow_mut();
+        let next = match &config.level {
+            None => 0,
+            Some(current) => self
+                .levels
+                .iter()
+                .position(|level| Rc::ptr_eq(level, current))
+                .map_or(0, |i| i + 1),
+        };
+        config.level = self.levels.get(next).cloned();
+        self.level_button.label = Self::level_label(config.level.as_deref());
+    }
+
+    fn fruit_label(fruit_count: usize) -> String {
+        format!("Fruit: {fruit_count}")
+    }
+
+    fn level_label(level: Option<&Level>) -> String {
+        format!("Level: {}", level.map_or("Open", |level| &level.name))
+    }
+
+    /// Moves on to the next size in `GRID_SIZES`. Levels are only made for
+    /// one size, so they are loaded again for the new grid and the chosen
+    /// one is kept if it still fits.
+    fn next_grid(&mut self) {
+        let mut config = self.config.borrow_mut();
+        let next = GRID_SIZES
+            .iter()
+            .position(|&(width, height)| {
+                width == config.grid.width && height == config.grid.height
+            })
+            .map_or(0, |i| (i + 1) % GRID_SIZES.len());
+        let (width, height) = GRID_SIZES[next];
+        config.grid = GridConfig { width, height };
+        self.grid_changed = true;
+
+        self.levels = Level::bundled(&config.grid)
+            .into_iter()
+            .map(Rc::new)
+            .collect();
+        config.level = config.level.take().and_then(|current| {
+            self.levels
+                .iter()
+                .find(|level| level.name == current.name)
+                .cloned()
+        });
+
+        self.grid_button.label = Self::grid_label(&config.grid);
+        self.level_button.label = Self::level_label(config.level.as_deref());
+    }
+
+    fn mode_label(mode: GameMode) -> String {
+        format!("Mode: {}", mode.name())
+    }
+
+    fn grid_label(grid: &GridConfig) -> String {
+        format!("Grid: {}x{}", grid.width, grid.height)
+    }
+
+    fn theme_label(theme: &Theme) -> String {
+        format!("Theme: {}", theme.name)
+    }
+
+    /// Writes the current settings back to `snek.toml` so the next launch
+    /// starts with them. Anything else already in the file is kept.
+    fn save_prefs(&self) {
+        let mut prefs = UserPrefs::load();
+        self.update_prefs(&mut prefs);
+        if let Err(e) = prefs.save() {
+            eprintln!("Failed to save settings: {e}");
+        }
+    }
+
+    fn update_prefs(&self, prefs: &mut UserPrefs) {
+        let config = self.config.borrow();
+        if self.grid_changed {
+            prefs.grid_width = Some(config.grid.width);
+            prefs.grid_height = Some(config.grid.height);
+        }
+        prefs.tick_speed_ms = u64::try_from(config.tick_speed_ms).ok();
+        prefs.wrap_mode = Some(config.wrap);
+        prefs.mouse_steering = Some(config.mouse_steering);
+        prefs.walls = Some(config.obstacles);
+        prefs.obstacle_count = Some(config.obstacle_count);
+        prefs.mode = Some(config.mode.name().to_owned());
+        prefs.fruit_count = Some(config.fruit_count);
+        prefs.level = config.level.as_ref().map(|level| level.name.clone());
+        prefs.theme = Some(self.theme.get().name.to_owned());
+        // Keys remapped by hand stay as they are until a preset is picked.
+        if config.input_map.name != "Custom" {
+            prefs.controls = Some(config.input_map.name.to_owned());
+            prefs.key_up = None;
+            prefs.key_down = None;
+            prefs.key_left = None;
+            prefs.key_right = None;
+        }
+    }
+}
+
+impl ButtonScene for Settings {
+    fn buttons(&self) -> Vec<&Button<Self>> {
+        vec![
+            &self.easy_button,
+            &self.medium_button,
+            &self.hard_button,
+            &self.wrap_button,
+            &self.obstacles_button,
+            &self.keys_button,
+            &self.level_button,
+            &self.fruit_button,
+            &self.grid_button,
+            &self.mode_button,
+            &self.theme_button,
+            &self.mouse_button,
+            &self.back_button,
+        ]
+    }
+
+    fn buttons_mut(&mut self) -> Vec<&mut Button<Self>> {
+        vec![
+            &mut self.easy_button,
+            &mut self.medium_button,
+            &mut self.hard_button,
+            &mut self.wrap_button,
+            &mut self.obstacles_button,
+            &mut self.keys_button,
+            &mut self.level_button,
+            &mut self.fruit_button,
+            &mut self.grid_button,
+            &mut self.mode_button,
+            &mut self.theme_button,
+            &mut self.mouse_button,
+            &mut self.back_button,
+        ]
+    }
+
+    fn focused_button_index(&mut self) -> &mut usize {
+        &mut self.focused_button_index
+    }
+}
+
+impl Scene for Settings {
+    fn update(&mut self) -> Option<SwapScene> {
+        self.poll_buttons().and_then(|on_click| on_click(self))
+    }
+
+    fn draw_background(&self, renderer: &Renderer) {
+        renderer.clear();
+        renderer.draw_starfield(&self.starfield);
+    }
+
+    fn draw(&self, renderer: &Renderer, _high_score: &HighScore) {
+        renderer.draw_text_centered(
+            &format!("Difficulty: {}", self.config.borrow().difficulty_name()),
+            60.,
+            50,
+            renderer.theme().text,
+        );
+        self.draw_buttons(renderer, self.focused_button_index);
+    }
+    fn on_enter(&mut self) {
+        self.focused_button_index = 0;
+    }
+
+    fn on_exit(&mut self) {
+        self.save_prefs();
+    }
+}
+
+/// Shown in place of whatever scene failed, so a broken frame ends the run
+/// with a message instead of a crash.
+struct ErrorScene {
+    exit_button: Button<Self>,
+    focused_button_index: usize,
+    message: String,
+}
+
+impl ErrorScene {
+    fn new(error: &GameError) -> Self {
+        let exit_button: Button<Self> = Button {
+            pos: Position { x: 250, y: 500 },
+            width: 300,
+            height: 100,
+            label: EXIT_LABEL.to_owned(),
+            shortcut: Some(KeyCode::Key1),
+            armed: false,
+            on_click: quit,
+        };
+        Self {
+            exit_button,
+            focused_button_index: 0,
+            message: error.to_string(),
+        }
+    }
+}
+
+impl ButtonScene for ErrorScene {
+    fn buttons(&self) -> Vec<&Button<Self>> {
+        vec![&self.exit_button]
+    }
+
+    fn buttons_mut(&mut self) -> Vec<&mut Button<Self>> {
+        vec![&mut self.exit_button]
+    }
+
+    fn focused_button_index(&mut self) -> &mut usize {
+        &mut self.focused_button_index
+    }
+}
+
+impl Scene for ErrorScene {
+    fn update(&mut self) -> Option<SwapScene> {
+        self.poll_buttons().and_then(|on_click| on_click(self))
+    }
+
+    fn draw(&self, renderer: &Renderer, _high_score: &HighScore) {
+        renderer.draw_text_centered(
+            "Something went wrong",
+            300.,
+            50,
+            renderer.theme().danger,
+        );
+        renderer.draw_text_centered(
+            &self.message,
+            360.,
+            30,
+            renderer.theme().text,
+        );
+        // The only button, so it always has focus.
+        self.draw_buttons(renderer, 0);
+    }
+}
+
+/// A point in time on macroquad's clock. `std::time::Instant` isn't
+/// available in the browser, so this stands in for it.
+#[derive(Clone, Copy)]
+struct Timestamp(f64);
+
+impl Timestamp {
+    #[cfg(not(test))]
+    fn now() -> Self {
+        Self(get_time())
+    }
+
+    /// macroquad's clock only runs once its window is up, so in tests
+    /// every timestamp is the same.
+    #[cfg(test)]
+    fn now() -> Self {
+        Self(0.)
+    }
+
+    fn elapsed(self) -> Duration {
+        Self::now().duration_since(self)
+    }
+
+    fn duration_since(self, earlier: Self) -> Duration {
+        Duration::from_secs_f64((self.0 - earlier.0).max(0.))
+    }
+}
+
+impl AddAssign<Duration> for Timestamp {
+    fn add_assign(&mut self, duration: Duration) {
+        self.0 += duration.as_secs_f64();
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum FruitKind {
+    Apple,
+    Berry,
+    Melon,
+}
+
+impl FruitKind {
+    /// How often each kind spawns, relative to the others.
+    const SPAWN_WEIGHTS: [(Self, u32); 3] =
+        [(Self::Apple, 70), (Self::Berry, 25), (Self::Melon, 5)];
+
+    fn random() -> Self {
+        Self::SPAWN_WEIGHTS
+            .choose_weighted(&mut rand::thread_rng(), |(_, weight)| *weight)
+            .map_or(Self::Apple, |(kind, _)| *kind)
+    }
+
+    fn points(self) -> u32 {
+        match self {
+            Self::Apple => 1,
+            Self::Berry => 3,
+            Self::Melon => 5,
+        }
+    }
+
+    /// Segments the snake gains from eating one.
+    fn growth(self) -> u32 {
+        match self {
+            Self::Apple => 1,
+            Self::Berry => 2,
+            Self::Melon => 3,
+        }
+    }
+}
+
+struct Fruit {
+    pos: Position,
+    kind: FruitKind,
+    /// Ticks left before a golden fruit expires, `None` for normal fruit.
+    golden_ticks_left: Option<u32>,
+    /// Ticks left before the fruit can be eaten.
+    telegraph_ticks_left: u32,
+}
+
+impl Fruit {
+    /// A fruit at `pos` that spawned while ticks were `tick_ms` long.
+    fn random(pos: Position, tick_ms: u128) -> Self {
+        let golden = rand::thread_rng().gen_ratio(1, GOLDEN_FRUIT_CHANCE);
+        Self {
+            pos,
+            kind: FruitKind::random(),
+            golden_ticks_left: golden.then_some(GOLDEN_FRUIT_TICKS),
+            telegraph_ticks_left: Self::telegraph_ticks(tick_ms),
+        }
+    }
+
+    /// Enough ticks of `tick_ms` to cover `FRUIT_TELEGRAPH_MS`, and at
+    /// least one. The count is fixed when the fruit spawns, so speeding up
+    /// afterwards shortens the telegraph a little.
+    fn telegraph_ticks(tick_ms: u128) -> u32 {
+        u32::try_from(FRUIT_TELEGRAPH_MS.div_ceil(tick_ms.max(1)))
+            .unwrap_or(u32::MAX)
+            .max(1)
+    }
+
+    /// Fresh fruit is only telegraphed at first and can't be eaten until
+    /// its telegraph ticks have run out.
+    fn is_active(&self) -> bool {
+        self.telegraph_ticks_left == 0
+    }
+
+    /// Counts the telegraph down by a tick. This happens at the start of
+    /// each tick, so a fruit is eaten if a head reaches it on the last of
+    /// its telegraph ticks, wherever it was placed from.
+    fn count_down_telegraph(&mut self) {
+        self.telegraph_ticks_left = self.telegraph_ticks_left.saturating_sub(1);
+    }
+
+    /// Counts a golden fruit down by a tick once it is active, returning
+    /// whether it just ran out. Counting ticks rather than time means
+    /// pausing doesn't run it out.
+    fn tick(&mut self) -> bool {
+        if !self.is_active() {
+            return false;
+        }
+        let Some(ticks_left) = &mut self.golden_ticks_left else {
+            return false;
+        };
+        *ticks_left = ticks_left.saturating_sub(1);
+        *ticks_left == 0
+    }
+
+    fn points(&self) -> u32 {
+        if self.golden_ticks_left.is_some() {
+            self.kind.points() * GOLDEN_FRUIT_MULTIPLIER
+        } else {
+            self.kind.points()
+        }
+    }
+}
+
+/// Who a snake belongs to.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Player {
+    One,
+    Two,
+    /// The opponent in a run against the computer.
+    Computer,
+}
+
+impl Player {
+    fn name(self) -> &'static str {
+        match self {
+            Self::One => "Player 1",
+            Self::Two => "Player 2",
+            Self::Computer => "Computer",
+        }
+    }
+
+    /// In a two-player run, player one steers with WASD and player two
+    /// with the arrow keys. The computer doesn't need keys.
+    fn input_map(self) -> Option<InputMap> {
+        let name = match self {
+            Self::One => "WASD",
+            Self::Two => "Arrows",
+            Self::Computer => return None,
+        };
+        InputMap::PRESETS
+            .into_iter()
+            .find(|preset| preset.name == name)
+    }
+}
+
+/// One snake and the turns queued up for it.
+struct SnakeState {
+    player: Player,
+    direction: Direction,
+    /// Turns waiting for upcoming ticks, oldest first.
+    next_direction: VecDeque<Direction>,
+    bodyparts: VecDeque<Position>,
+    /// The body as it was before the last tick, for drawing the move
+    /// between the two.
+    previous_bodyparts: VecDeque<Position>,
+    head_position: Position,
+    /// Segments still to be added from fruit already eaten, one per tick.
+    pending_growth: u32,
+    score: u32,
+    /// Set on the tick the snake crashes, which ends the run.
+    dead: bool,
+}
+
+impl SnakeState {
+    fn new(
+        player: Player,
+        grid: &GridConfig,
+        head: Position,
+        length: usize,
+    ) -> Self {
+        let bodyparts = Self::starting_body(grid, &head, length);
+        Self {
+            player,
+            direction: Direction::Up,
+            next_direction: VecDeque::new(),
+            previous_bodyparts: bodyparts.clone(),
+            bodyparts,
+            head_position: head,
+            pending_growth: 0,
+            score: 0,
+            dead: false,
+        }
+    }
+
+    /// Lays the snake out in a vertical line below `head`, facing up. The
+    /// length is cut short if it wouldn't fit above the bottom wall.
+    fn starting_body(
+        grid: &GridConfig,
+        head: &Position,
+        length: usize,
+    ) -> VecDeque<Position> {
+        let length = i32::try_from(length)
+            .unwrap_or(i32::MAX)
+            .clamp(1, grid.height - head.y);
+
+        (0..length)
+            .rev()
+            .map(|i| Position {
+                x: head.x,
+                y: head.y + i,
+            })
+            .collect()
+    }
+
+    /// Takes the next queued turn and moves the head a cell on. The body
+    /// is left for the scene to catch up once it knows what the head ran
+    /// into.
+    fn tick(&mut self) {
+        self.previous_bodyparts = self.bodyparts.clone();
+
+        if let Some(direction) = self.next_direction.pop_front() {
+            self.direction = direction;
+        }
+
+        match self.direction {
+            Direction::Up => self.head_position.y -= 1,
+            Direction::Left => self.head_position.x -= 1,
+            Direction::Down => self.head_position.y += 1,
+            Direction::Right => self.head_position.x += 1,
+        }
+    }
+
+    /// Keeping the tail where it is grows the snake by one segment.
+    fn move_tail(&mut self) {
+        if self.pending_growth > 0 {
+            self.pending_growth -= 1;
+        } else {
+            self.bodyparts.pop_front();
+        }
+    }
+
+    /// Whether the head or any of the body is on `cell`.
+    fn occupies(&self, cell: &Position) -> bool {
+        self.head_position == *cell || self.bodyparts.contains(cell)
+    }
+
+    /// Queues a turn for an upcoming tick. Turns are checked against the
+    /// last queued direction rather than the current one, so two quick
+    /// presses can't add up to a reversal.
+    fn queue_direction(&mut self, direction: Direction) {
+        let last = self.next_direction.back().unwrap_or(&self.direction);
+
+        if self.next_direction.len() < INPUT_QUEUE_CAPACITY
+            && *last != direction
+            && last.opposite() != direction
+        {
+            self.next_direction.push_back(direction);
+        }
+    }
+
+    /// Steers along whichever axis the target is further away on, as long
+    /// as that doesn't reverse the snake into itself.
+    fn steer_towards(&mut self, target: &Position) {
+        let dx = target.x - self.head_position.x;
+        let dy = target.y - self.head_position.y;
+
+        let wanted = if dx.abs() >= dy.abs() && dx != 0 {
+            if dx < 0 {
+                Direction::Left
+            } else {
+                Direction::Right
+            }
+        } else if dy < 0 {
+            Direction::Up
+        } else if dy > 0 {
+            Direction::Down
+        } else {
+            return;
+        };
+
+        self.queue_direction(wanted);
+    }
+
+    /// Where the `i`th segment from the head was before the last tick and
+    /// where it is now. A segment that was just cut off the tail moves
+    /// towards the one in front of it, so it slides out instead of
+    /// vanishing, and one that was just grown stays put.
+    fn segment_motion(&self, i: usize) -> Option<(&Position, &Position)> {
+        let current = Self::nth_from_head(&self.bodyparts, i);
+        let previous = Self::nth_from_head(&self.previous_bodyparts, i);
+
+        let from = previous.or(current)?;
+        let to = current.or_else(|| {
+            i.checked_sub(1)
+                .and_then(|i| Self::nth_from_head(&self.previous_bodyparts, i))
+        })?;
+        Some((from, to))
+    }
+
+    fn nth_from_head(
+        bodyparts: &VecDeque<Position>,
+        i: usize,
+    ) -> Option<&Position> {
+        bodyparts
+            .len()
+            .checked_sub(i + 1)
+            .and_then(|i| bodyparts.get(i))
+    }
+}
+
+/// A snake the computer steers, which goes for the fruit by the shortest
+/// safe path it can find.
+struct AiSnake {
+    state: SnakeState,
+}
+
+impl AiSnake {
+    /// Heads along the shortest path to `fruit` around `obstacles`. With
+    /// no way there it takes any step that's safe for now, and with none of
+    /// those it keeps going.
+    fn choose_direction(
+        state: &SnakeState,
+        fruit: &Position,
+        obstacles: &[Position],
+        grid: &GridConfig,
+    ) -> Direction {
+        let head = &state.head_position;
+        let next =
+            pathfinding::bfs(*head, *fruit, obstacles, grid.width, grid.height)
+                .and_then(|path| path.into_iter().next());
+        if let Some(direction) = next.and_then(|next| {
+            let step = next - *head;
+            Direction::from_delta(step.x, step.y)
+        }) {
+            return direction;
+        }
+
+        Direction::ALL
+            .into_iter()
+            .zip(head.neighbors(grid.width, grid.height))
+            .filter(|(direction, cell)| {
+                *direction != state.direction.opposite()
+                    && cell
+                        .as_ref()
+                        .is_some_and(|cell| !obstacles.contains(cell))
+            })
+            .map(|(direction, _)| direction)
+            .next()
+            .unwrap_or_else(|| state.direction.clone())
+    }
+}
+
+/// A crash playing out before the game over screen.
+struct Dying {
+    since: Timestamp,
+    results: RunResults,
+}
+
+impl Dying {
+    fn is_over(&self) -> bool {
+        self.since.elapsed().as_millis() >= DEATH_ANIMATION_MS
+    }
+
+    fn flash_on(&self) -> bool {
+        (self.since.elapsed().as_millis() / DEATH_FLASH_MS).is_multiple_of(2)
+    }
+
+    /// How many of a snake's `segments` are still shown, counting from
+    /// the head, as they disappear one after another from the tail.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    fn segments_left(&self, segments: usize) -> usize {
+        let progress =
+            self.since.elapsed().as_millis() as f32 / DEATH_ANIMATION_MS as f32;
+        (segments as f32 * (1. - progress.min(1.))).ceil() as usize
+    }
+}
+
+struct GameScene {
+    grid: GridConfig,
+    /// The one snake, or one per player.
+    snakes: Vec<SnakeState>,
+    /// The computer's snake, in a run against it.
+    ai: Option<AiSnake>,
+    /// Time owed to the simulation that doesn't yet add up to a tick.
+    tick_accumulator: Duration,
+    fruits: Vec<Fruit>,
+    /// Shrinks the snake by a segment when eaten.
+    poison_fruit: Option<Position>,
+    /// Ticks left before the poison fruit disappears.
+    poison_lifetime: u32,
+    /// Doubles the speed for a while when eaten.
+    speedboost: Option<Position>,
+    /// Ticks left before the speed boost disappears.
+    speedboost_lifetime: u32,
+    /// Ticks left before the next speed boost shows up.
+    ticks_until_speedboost: u32,
+    /// Ticks the snake has left at double speed.
+    boost_ticks_remaining: u32,
+    obstacles: HashSet<Position>,
+    /// Pairs of cells that send the head from either one to the other.
+    portals: Vec<(Position, Position)>,
+    /// Ticks until the play area next shrinks.
+    shrink_timer: u32,
+    /// Size of the play area, which only starts out smaller than the grid
+    /// when the grid is shrinking.
+    effective_width: i32,
+    effective_height: i32,
+    config: GameConfig,
+    /// Where each finger on the screen first touched down.
+    touch_starts: HashMap<u64, Vec2>,
+    fruits_eaten: u32,
+    /// Time played so far, leaving out the countdown and any pauses.
+    play_time: Duration,
+    paused_since: Option<Timestamp>,
+    /// Time since the run started, only counted until the countdown is
+    /// over.
+    countdown_elapsed: Duration,
+    /// Multiplies the points for each fruit eaten in quick succession.
+    combo: u32,
+    /// When the last fruit was eaten.
+    combo_timer: Timestamp,
+    /// Play time left in a timed run.
+    time_left: Duration,
+    /// Fruit eaten so far in the current quota window.
+    fruits_in_window: u32,
+    /// Ticks so far in the current quota window.
+    window_tick_count: u32,
+    /// Set once a player has crashed, until the game over screen takes
+    /// over.
+    dying: Option<Dying>,
+    /// The way to the nearest fruit while the hint is shown.
+    hint: Option<Vec<Position>>,
+    /// Keeps the hint on without holding H. Toggled with Tab and kept from
+    /// one run to the next.
+    path_assist: bool,
+    /// Set when a tick has moved things on since `hint` was worked out.
+    hint_stale: bool,
+}
+impl Scene for GameScene {
+    fn update(&mut self) -> Option<SwapScene> {
+        // Nothing moves and input is ignored while a crash plays out.
+        if let Some(dying) = &self.dying {
+            if !dying.is_over() {
+                return None;
+            }
+            return self
+                .dying
+                .take()
+                .map(|dying| SwapScene::GameOver(dying.results));
+        }
+
+        if is_key_pressed(KeyCode::Escape)
+            || is_key_pressed(KeyCode::P)
+            || is_key_pressed(KeyCode::Space)
+        {
+            self.toggle_pause();
+        }
+        if self.paused_since.is_some() {
+            return None;
+        }
+        // A save only holds one snake.
+        if self.snakes.len() == 1 && self.ai.is_none() {
+            if is_key_pressed(KeyCode::F5) {
+                if let Err(e) = self.save().save() {
+                    eprintln!("Failed to save the game: {e}");
+                }
+            }
+            if is_key_pressed(KeyCode::F9) {
+                match SaveState::load(&self.grid, &self.obstacles) {
+                    Ok(state) => self.load(state),
+                    Err(e) => eprintln!("Failed to load the game: {e}"),
+                }
+            }
+        }
+
+        if is_key_pressed(KeyCode::Tab) {
+            self.path_assist = !self.path_assist;
+        }
+        // Turns can be queued during the countdown, but the snake waits.
+        self.handle_input();
+        self.refresh_hint();
+
+        let frame_time = Duration::from_secs_f32(get_frame_time());
+        if !self.run_clock(frame_time) {
+            return None;
+        }
+
+        if let GameMode::Timed { .. } = self.config.mode {
+            self.time_left = self.time_left.saturating_sub(frame_time);
+            if self.time_left.is_zero() {
+                return Some(SwapScene::GameOver(self.results()));
+            }
+        }
+
+        self.tick_accumulator += frame_time;
+        let mut steps = 0;
+        while self.dying.is_none()
+            && self.tick_accumulator >= self.tick_duration()
+        {
+            self.tick_accumulator -= self.tick_duration();
+            if let Some(swap) = self.step() {
+                return Some(swap);
+            }
+
+            // After a long stall, drop the time still owed rather than
+            // spending every frame catching up.
+            steps += 1;
+            if steps == MAX_TICKS_PER_FRAME {
+                self.tick_accumulator = Duration::ZERO;
+            }
+        }
+        if steps > 0 {
+            self.refresh_hint();
+        }
+        None
+    }
+
+    fn draw_background(&self, renderer: &Renderer) {
+        renderer.clear();
+        renderer.draw_grid();
+    }
+
+    fn draw(&self, renderer: &Renderer, _high_score: &HighScore) {
+        if self.config.mouse_steering
+            && self.paused_since.is_none()
+            && touches().is_empty()
+            && is_mouse_button_down(MouseButton::Left)
+        {
+            renderer.draw_steering_target(&self.mouse_cell());
+        }
+        // Under the snakes so it never hides them.
+        if let Some(path) = &self.hint {
+            renderer.draw_hint(path);
+        }
+
+        // Drawn tail first so the head ends up on top, and so a tail
+        // segment that is sliding out is hidden under the next one.
+        let progress = self.tick_progress();
+        let ai = self.ai.as_ref().map(|ai| &ai.state);
+        for snake in self.snakes.iter().chain(ai) {
+            let segments =
+                snake.bodyparts.len().max(snake.previous_bodyparts.len());
+            let dying = self.dying.as_ref().filter(|_| snake.dead);
+            // With reduced motion a crashed snake stays whole and doesn't
+            // flash, only changing colour.
+            let shown = match dying {
+                Some(dying) if !renderer.reduce_motion => {
+                    dying.segments_left(segments)
+                }
+                _ => segments,
+            };
+            let crash_colored = dying.is_some_and(|dying| {
+                renderer.reduce_motion || dying.flash_on()
+            });
+            for i in (0..shown).rev() {
+                let Some((from, to)) = snake.segment_motion(i) else {
+                    continue;
+                };
+                if crash_colored {
+                    renderer.draw_crashed_segment(to);
+                } else if i == 0 {
+                    renderer.draw_head(
+                        snake.player,
+                        &snake.direction,
+                        from,
+                        to,
+                        progress,
+                    );
+                } else {
+                    renderer.draw_bodypart(
+                        snake.player,
+                        i,
+                        segments,
+                        from,
+                        to,
+                        progress,
+                    );
+                }
+            }
+        }
+
+        for fruit in &self.fruits {
+            if fruit.is_active() {
+                renderer.draw_fruit(fruit);
+            } else {
+                renderer.draw_fruit_telegraph(fruit);
+            }
+        }
+        for obstacle in &self.obstacles {
+            renderer.draw_obstacle(obstacle);
+        }
+        for (a, b) in &self.portals {
+            renderer.draw_portal_pair(a, b);
+        }
+        if self.config.shrinking {
+            renderer.draw_inactive_zone(
+                &self.grid,
+                &self.play_area_origin(),
+                self.effective_width,
+                self.effective_height,
+            );
+        }
+        if let Some(poison_fruit) = &self.poison_fruit {
+            renderer.draw_poison_fruit(poison_fruit);
+        }
+        if let Some(speedboost) = &self.speedboost {
+            renderer.draw_speedboost(speedboost);
+        }
+        if self.dying.is_some() {
+            for snake in self.snakes.iter().filter(|snake| snake.dead) {
+                renderer
+                    .draw_crash_site(&self.clamp_to_grid(&snake.head_position));
+            }
+        }
+
+        renderer.draw_hud(&self.snakes, self.current_combo());
+        match self.config.mode {
+            GameMode::Classic => {}
+            GameMode::Timed { .. } => renderer.draw_time_left(self.time_left),
+            GameMode::Quota {
+                fruits_needed,
+                window_ticks,
+            } => renderer.draw_quota_meter(
+                self.fruits_in_window,
+                fruits_needed,
+                window_ticks.saturating_sub(self.window_tick_count),
+                window_ticks,
+            ),
+        }
+
+        if self.paused_since.is_some() {
+            renderer.draw_pause_overlay();
+        } else if let Some(label) = self.countdown_label() {
+            renderer.draw_countdown(label);
+        }
+    }
+    fn on_enter(&mut self) {
+        self.grid = self.config.grid;
+        self.effective_width = self.grid.width;
+        self.effective_height = self.grid.height;
+        self.shrink_timer = SHRINK_INTERVAL_TICKS;
+        let level = self.config.level.clone();
+
+        self.snakes = self
+            .starting_heads(level.as_deref())
+            .into_iter()
+            .map(|(player, head)| {
+                SnakeState::new(
+                    player,
+                    &self.grid,
+                    head,
+                    self.config.initial_length,
+                )
+            })
+            .collect();
+
+        self.obstacles = if let Some(level) = &level {
+            level.walls.clone()
+        } else if self.config.obstacles {
+            Self::obstacle_layout(&self.grid)
+        } else {
+            HashSet::new()
+        };
+        // Levels only mark where player one starts, so clear the way for
+        // player two.
+        let snakes = &self.snakes;
+        self.obstacles
+            .retain(|wall| !snakes.iter().any(|snake| snake.occupies(wall)));
+        self.poison_fruit = None;
+        self.speedboost = None;
+        self.speedboost_lifetime = 0;
+        self.ticks_until_speedboost = SPEEDBOOST_INTERVAL_TICKS;
+        self.boost_ticks_remaining = 0;
+
+        self.fruits.clear();
+        if let Some(fruit) =
+            level.as_ref().and_then(|level| level.fruit.as_ref())
+        {
+            self.fruits
+                .push(Fruit::random(*fruit, self.current_tick_ms()));
+        }
+        for _ in self.fruits.len()..self.config.fruit_count {
+            self.place_fruit();
+        }
+        self.portals.clear();
+        // A level's layout is fixed, and random walls come with the walls
+        // being on.
+        if level.is_none() && self.config.obstacles {
+            self.place_random_obstacles();
+        }
+        self.place_portals();
+        self.ai = None;
+        if self.config.vs_computer {
+            self.ai = self.spawn_ai();
+        }
+        self.touch_starts.clear();
+        self.fruits_eaten = 0;
+        self.tick_accumulator = Duration::ZERO;
+        self.play_time = Duration::ZERO;
+        self.paused_since = None;
+        self.countdown_elapsed = Duration::ZERO;
+        // Starting at 0 makes the first fruit count once either way.
+        self.combo = 0;
+        self.combo_timer = Timestamp::now();
+        self.time_left = match self.config.mode {
+            GameMode::Timed { seconds } => {
+                Duration::from_secs(u64::from(seconds))
+            }
+            _ => Duration::ZERO,
+        };
+        self.fruits_in_window = 0;
+        self.window_tick_count = 0;
+        self.dying = None;
+        self.hint = None;
+        self.hint_stale = true;
+    }
+
+    fn set_config(&mut self, config: &GameConfig) {
+        self.config = config.clone();
+    }
+}
+
+impl GameScene {
+    fn new(config: &Config) -> Self {
+        let grid = &config.grid;
+        let config = GameConfig::default();
+
+        let head = Position {
+            x: grid.width / 2,
+            y: grid.height / 2,
+        };
+
+        Self {
+            grid: *grid,
+            snakes: vec![SnakeState::new(
+                Player::One,
+                grid,
+                head,
+                config.initial_length,
+            )],
+            ai: None,
+            tick_accumulator: Duration::ZERO,
+            fruits: Vec::new(),
+            poison_fruit: None,
+            poison_lifetime: 0,
+            speedboost: None,
+            speedboost_lifetime: 0,
+            ticks_until_speedboost: SPEEDBOOST_INTERVAL_TICKS,
+            boost_ticks_remaining: 0,
+            obstacles: HashSet::new(),
+            portals: Vec::new(),
+            shrink_timer: SHRINK_INTERVAL_TICKS,
+            effective_width: grid.width,
+            effective_height: grid.height,
+            config,
+            touch_starts: HashMap::new(),
+            fruits_eaten: 0,
+            play_time: Duration::ZERO,
+            paused_since: None,
+            countdown_elapsed: Duration::ZERO,
+            combo: 0,
+            combo_timer: Timestamp::now(),
+            time_left: Duration::ZERO,
+            fruits_in_window: 0,
+            window_tick_count: 0,
+            dying: None,
+            hint: None,
+            hint_stale: true,
+            path_assist: false,
+        }
+    }
+
+    /// Where each snake's head starts. A lone snake starts in the middle
+    /// or wherever the level says. Two start a third of the way in from
+    /// either side, or on a level, with player two a quarter of the grid
+    /// across from the start.
+    fn starting_heads(&self, level: Option<&Level>) -> Vec<(Player, Position)> {
+        let (width, middle) = (self.grid.width, self.grid.height / 2);
+        match (level, self.config.two_player) {
+            (None, false) => vec![(
+                Player::One,
+                Position {
+                    x: width / 2,
+                    y: middle,
+                },
+            )],
+            (None, true) => vec![
+                (
+                    Player::One,
+                    Position {
+                        x: width / 3,
+                        y: middle,
+                    },
+                ),
+                (
+                    Player::Two,
+                    Position {
+                        x: width - 1 - width / 3,
+                        y: middle,
+                    },
+                ),
+            ],
+            (Some(level), two_player) => {
+                let start = &level.start;
+                let mut heads = vec![(Player::One, *start)];
+                if two_player {
+                    let x = if start.x + width / 4 < width {
+                        start.x + width / 4
+                    } else {
+                        start.x - width / 4
+                    };
+                    heads.push((Player::Two, Position { x, y: start.y }));
+                }
+                heads
+            }
+        }
+    }
+
+    /// Player one's snake and where the fruit is.
+    fn save(&self) -> SaveState {
+        let snake = &self.snakes[0];
+        SaveState {
+            bodyparts: snake.bodyparts.clone(),
+            head_position: snake.head_position,
+            fruit_locations: self
+                .fruits
+                .iter()
+                .map(|fruit| fruit.pos)
+                .collect(),
+            score: snake.score,
+            direction: snake.direction.clone(),
+        }
+    }
+
+    /// Puts player one's snake and the fruit back where a save had them.
+    /// Fruit comes back as a random kind, since the save doesn't say.
+    fn load(&mut self, state: SaveState) {
+        let snake = &mut self.snakes[0];
+        snake.previous_bodyparts = state.bodyparts.clone();
+        snake.bodyparts = state.bodyparts;
+        snake.head_position = state.head_position;
+        snake.direction = state.direction;
+        snake.next_direction.clear();
+        snake.pending_growth = 0;
+        snake.score = state.score;
+        snake.dead = false;
+
+        let tick_ms = self.current_tick_ms();
+        self.fruits = state
+            .fruit_locations
+            .into_iter()
+            .map(|pos| Fruit::random(pos, tick_ms))
+            .collect();
+        self.hint_stale = true;
+        for _ in self.fruits.len()..self.config.fruit_count {
+            self.place_fruit();
+        }
+        self.tick_accumulator = Duration::ZERO;
+    }
+
+    /// Moves the game on by one tick.
+    fn step(&mut self) -> Option<SwapScene> {
+        self.hint_stale = true;
+        for fruit in &mut self.fruits {
+            fruit.count_down_telegraph();
+        }
+        self.move_ai();
+        for snake in &mut self.snakes {
+            snake.tick();
+        }
+
+        for i in 0..self.snakes.len() {
+            if self.config.wrap {
+                self.snakes[i].head_position =
+                    self.wrap_into_play_area(&self.snakes[i].head_position);
+            } else if !self.in_play_area(&self.snakes[i].head_position) {
+                self.snakes[i].dead = true;
+                continue;
+            }
+
+            // Only the head goes through; the body catches up over the next
+            // few ticks.
+            if let Some(exit) = self.portal_exit(&self.snakes[i].head_position)
+            {
+                self.snakes[i].head_position = exit;
+            }
+
+            if self.obstacles.contains(&self.snakes[i].head_position) {
+                self.snakes[i].dead = true;
+            }
+        }
+
+        for i in 0..self.snakes.len() {
+            if let Some(swap) = self.eat_fruit(i) {
+                return Some(swap);
+            }
+        }
+        // Players get the fruit if they arrive on the same tick.
+        self.ai_eat_fruit();
+
+        // Missed golden fruit turns into a normal one somewhere else.
+        for i in (0..self.fruits.len()).rev() {
+            if self.fruits[i].tick() {
+                self.fruits.swap_remove(i);
+                if self.place_fruit() {
+                    if let Some(fruit) = self.fruits.last_mut() {
+                        fruit.golden_ticks_left = None;
+                    }
+                }
+            }
+        }
+
+        let ai = self.ai.as_mut().map(|ai| &mut ai.state);
+        for snake in self.snakes.iter_mut().chain(ai) {
+            if !snake.dead {
+                snake.move_tail();
+            }
+        }
+
+        let poisoned = self.snakes.iter_mut().find(|snake| {
+            !snake.dead
+                && self.poison_fruit.as_ref() == Some(&snake.head_position)
+        });
+        if let Some(snake) = poisoned {
+            self.poison_fruit = None;
+            snake.score = snake.score.saturating_sub(POISON_FRUIT_PENALTY);
+            // A snake that is only its head has nothing left to lose.
+            if snake.bodyparts.pop_front().is_none() {
+                snake.dead = true;
+            }
+        } else if self.poison_fruit.is_some() {
+            self.poison_lifetime = self.poison_lifetime.saturating_sub(1);
+            if self.poison_lifetime == 0 {
+                self.poison_fruit = None;
+            }
+        }
+
+        self.tick_speedboost();
+
+        // Worked out for every snake before any is marked, so two heads
+        // meeting take each other out.
+        let crashed: Vec<usize> = (0..self.snakes.len())
+            .filter(|&i| !self.snakes[i].dead && self.crashed(i))
+            .collect();
+        for i in crashed {
+            self.snakes[i].dead = true;
+        }
+        self.finish_ai_tick();
+        if self.snakes.iter().any(|snake| snake.dead) {
+            self.dying = Some(Dying {
+                since: Timestamp::now(),
+                results: self.results(),
+            });
+            return None;
+        }
+        for snake in &mut self.snakes {
+            let head = &snake.head_position;
+            snake.bodyparts.push_back(*head);
+        }
+
+        if let GameMode::Quota {
+            fruits_needed,
+            window_ticks,
+        } = self.config.mode
+        {
+            self.window_tick_count += 1;
+            if self.window_tick_count >= window_ticks {
+                if self.fruits_in_window < fruits_needed {
+                    return Some(SwapScene::GameOver(self.results()));
+                }
+                self.window_tick_count = 0;
+                self.fruits_in_window = 0;
+            }
+        }
+
+        if self.config.shrinking {
+            self.shrink_timer = self.shrink_timer.saturating_sub(1);
+            if self.shrink_timer == 0 {
+                self.shrink_play_area();
+            }
+        }
+        None
+    }
+
+    /// Eats whatever fruit the `i`th snake's head is on. Returns the end
+    /// of the run if that fruit filled the grid.
+    fn eat_fruit(&mut self, i: usize) -> Option<SwapScene> {
+        if self.snakes[i].dead {
+            return None;
+        }
+        let head = &self.snakes[i].head_position;
+        let index = self.fruits.iter().position(|fruit| fruit.pos == *head)?;
+
+        let fruit = self.fruits.swap_remove(index);
+        if !fruit.is_active() {
+            // Arriving before the telegraph ends moves the spawn
+            // elsewhere instead of handing out a free fruit.
+            self.place_fruit();
+            return None;
+        }
+
+        if self.combo_timer.elapsed().as_millis() < COMBO_WINDOW_MS {
+            self.combo += 1;
+        } else {
+            self.combo = 1;
+        }
+        self.combo_timer = Timestamp::now();
+        let snake = &mut self.snakes[i];
+        snake.score += fruit.points() * self.combo;
+        snake.pending_growth += fruit.kind.growth();
+        self.fruits_eaten += 1;
+        self.fruits_in_window += 1;
+        // Other fruit can still be eaten while there's no room for a new
+        // one; the grid is only full once they're gone.
+        if !self.place_fruit() && self.fruits.is_empty() {
+            let snake = &self.snakes[i];
+            let winner = (self.snakes.len() > 1).then_some(snake.player);
+            return Some(SwapScene::GameOver(RunResults {
+                won: true,
+                length: snake.previous_bodyparts.len() + 1,
+                ..self.results_for(snake, winner)
+            }));
+        }
+        if self.poison_fruit.is_none()
+            && self
+                .fruits_eaten
+                .checked_rem(self.config.poison_fruit_every)
+                == Some(0)
+        {
+            self.place_poison_fruit();
+        }
+        None
+    }
+
+    /// Whether the `i`th snake's head has run into a body, its own or
+    /// another's, or into another player's head. Running into the
+    /// computer's head is its problem, not the player's.
+    fn crashed(&self, i: usize) -> bool {
+        let head = &self.snakes[i].head_position;
+        self.snakes.iter().enumerate().any(|(j, other)| {
+            other.bodyparts.contains(head)
+                || (j != i && !other.dead && other.head_position == *head)
+        }) || self
+            .ai
+            .as_ref()
+            .is_some_and(|ai| ai.state.bodyparts.contains(head))
+    }
+
+    fn occupied_by_snake(&self, cell: &Position) -> bool {
+        self.snakes.iter().any(|snake| snake.occupies(cell))
+            || self.ai.as_ref().is_some_and(|ai| ai.state.occupies(cell))
+    }
+
+    /// Puts the computer's snake down on a free cell, one segment long.
+    fn spawn_ai(&self) -> Option<AiSnake> {
+        let head = self.new_fruit_position(&[
+            self.poison_fruit.as_ref(),
+            self.speedboost.as_ref(),
+        ])?;
+        Some(AiSnake {
+            state: SnakeState::new(Player::Computer, &self.grid, head, 1),
+        })
+    }
+
+    /// Cells a path to the fruit has to go round: walls, anything outside
+    /// the play area and every snake.
+    fn blocked_cells(&self) -> Vec<Position> {
+        (0..self.grid.height)
+            .flat_map(|y| (0..self.grid.width).map(move |x| Position { x, y }))
+            .filter(|cell| {
+                !self.in_play_area(cell)
+                    || self.obstacles.contains(cell)
+                    || self.occupied_by_snake(cell)
+            })
+            .collect()
+    }
+
+    fn nearest_fruit(&self, cell: &Position) -> Option<&Fruit> {
+        self.fruits
+            .iter()
+            .filter(|fruit| fruit.is_active())
+            .min_by_key(|fruit| fruit.pos.manhattan_distance(cell))
+    }
+
+    /// Whether the hint is switched on with Tab or H is held for it. Each
+    /// player has fixed keys in a two player run, but on their own one
+    /// might steer with H, as with the Vim keys, and then holding it
+    /// doesn't count.
+    fn hint_wanted(&self) -> bool {
+        self.path_assist
+            || is_key_down(KeyCode::H)
+                && (self.config.two_player
+                    || !self.config.input_map.binds(KeyCode::H))
+    }
+
+    /// Keeps the hint up to date while it's wanted. The path is only
+    /// worked out again once a tick has moved things on.
+    fn refresh_hint(&mut self) {
+        if !self.hint_wanted() {
+            self.hint = None;
+            self.hint_stale = true;
+        } else if self.hint_stale {
+            self.hint = self.hint_path();
+            self.hint_stale = false;
+        }
+    }
+
+    /// The way from player one's head to the nearest fruit.
+    fn hint_path(&self) -> Option<Vec<Position>> {
+        let head = &self.snakes.first()?.head_position;
+        let fruit = self.nearest_fruit(head)?;
+
+        pathfinding::bfs(
+            *head,
+            fruit.pos,
+            &self.blocked_cells(),
+            self.grid.width,
+            self.grid.height,
+        )
+    }
+
+    /// Points the computer's snake at the nearest fruit and moves its head,
+    /// marking it dead if that took it off the play area or into a wall.
+    fn move_ai(&mut self) {
+        let Some(mut ai) = self.ai.take() else {
+            return;
+        };
+
+        if let Some(fruit) = self.nearest_fruit(&ai.state.head_position) {
+            ai.state.direction = AiSnake::choose_direction(
+                &ai.state,
+                &fruit.pos,
+                &self.blocked_cells(),
+                &self.grid,
+            );
+        }
+        ai.state.tick();
+
+        if self.config.wrap {
+            ai.state.head_position =
+                self.wrap_into_play_area(&ai.state.head_position);
+        } else if !self.in_play_area(&ai.state.head_position) {
+            ai.state.dead = true;
+        }
+        if let Some(exit) = self.portal_exit(&ai.state.head_position) {
+            ai.state.head_position = exit;
+        }
+        if self.obstacles.contains(&ai.state.head_position) {
+            ai.state.dead = true;
+        }
+        self.ai = Some(ai);
+    }
+
+    /// The computer grows from fruit like anyone else, but scores nothing
+    /// for it.
+    fn ai_eat_fruit(&mut self) {
+        let Some(ai) = self.ai.as_mut().filter(|ai| !ai.state.dead) else {
+            return;
+        };
+        let head = &ai.state.head_position;
+        let Some(index) =
+            self.fruits.iter().position(|fruit| fruit.pos == *head)
+        else {
+            return;
+        };
+
+        let fruit = self.fruits.swap_remove(index);
+        if fruit.is_active() {
+            ai.state.pending_growth += fruit.kind.growth();
+        }
+        self.place_fruit();
+    }
+
+    /// Pushes the computer's new head on, or starts it over somewhere else
+    /// if it crashed into a wall, a player or itself.
+    fn finish_ai_tick(&mut self) {
+        let Some(mut ai) = self.ai.take() else {
+            return;
+        };
+
+        let head = &ai.state.head_position;
+        if ai.state.dead
+            || ai.state.bodyparts.contains(head)
+            || self.snakes.iter().any(|snake| snake.occupies(head))
+        {
+            self.ai = self.spawn_ai();
+            return;
+        }
+        ai.state.bodyparts.push_back(*head);
+        self.ai = Some(ai);
+    }
+
+    fn wrap_into_play_area(&self, p: &Position) -> Position {
+        let origin = self.play_area_origin();
+        Position {
+            x: origin.x + (p.x - origin.x).rem_euclid(self.effective_width),
+            y: origin.y + (p.y - origin.y).rem_euclid(self.effective_height),
+        }
+    }
+
+    /// Where the play area starts. It shrinks towards the middle of the
+    /// grid, a side at a time.
+    fn play_area_origin(&self) -> Position {
+        Position {
+            x: (self.grid.width - self.effective_width) / 2,
+            y: (self.grid.height - self.effective_height) / 2,
+        }
+    }
+
+    fn in_play_area(&self, p: &Position) -> bool {
+        let origin = self.play_area_origin();
+        (origin.x..origin.x + self.effective_width).contains(&p.x)
+            && (origin.y..origin.y + self.effective_height).contains(&p.y)
+    }
+
+    /// The nearest cell on the grid, so running off the edge can be shown
+    /// on the edge it went over.
+    fn clamp_to_grid(&self, p: &Position) -> Position {
+        Position {
+            x: p.x.clamp(0, self.grid.width - 1),
+            y: p.y.clamp(0, self.grid.height - 1),
+        }
+    }
+
+    /// Takes a row and a column off the play area. Fruit left outside moves
+    /// back in, and pickups and portals left outside are lost.
+    fn shrink_play_area(&mut self) {
+        self.shrink_timer = SHRINK_INTERVAL_TICKS;
+        self.effective_width = (self.effective_width - 1)
+            .max(SHRINK_MIN_SIZE.min(self.grid.width));
+        self.effective_height = (self.effective_height - 1)
+            .max(SHRINK_MIN_SIZE.min(self.grid.height));
+
+        for i in (0..self.fruits.len()).rev() {
+            if !self.in_play_area(&self.fruits[i].pos) {
+                self.fruits.swap_remove(i);
+                self.place_fruit();
+            }
+        }
+        if self
+            .poison_fruit
+            .as_ref()
+            .is_some_and(|p| !self.in_play_area(p))
+        {
+            self.poison_fruit = None;
+        }
+        if self
+            .speedboost
+            .as_ref()
+            .is_some_and(|p| !self.in_play_area(p))
+        {
+            self.speedboost = None;
+        }
+        let portals = std::mem::take(&mut self.portals);
+        self.portals = portals
+            .into_iter()
+            .filter(|(a, b)| self.in_play_area(a) && self.in_play_area(b))
+            .collect();
+    }
+
+    /// The snake speeds up with every fruit until it hits the floor, and
+    /// goes twice as fast again while boosted. With two snakes, the one in
+    /// the lead sets the pace.
+    fn current_tick_ms(&self) -> u128 {
+        let score = self.snakes.iter().map(|snake| snake.score).max();
+        let tick_ms = self
+            .config
+            .tick_speed_ms
+            .saturating_sub(
+                u128::from(score.unwrap_or(0)) * SPEED_INCREMENT_PER_SCORE,
+            )
+            .max(MIN_TICK_MS);
+
+        if self.boost_ticks_remaining > 0 {
+            tick_ms / 2
+        } else {
+            tick_ms
+        }
+    }
+
+    /// What the countdown is showing, or `None` once it is gone.
+    fn countdown_label(&self) -> Option<&'static str> {
+        let step = self.countdown_elapsed.as_millis() / COUNTDOWN_STEP_MS;
+        COUNTDOWN_LABELS.get(usize::try_from(step).ok()?).copied()
+    }
+
+    /// Moves the countdown on by `frame_time`, or the play time once the
+    /// countdown is over, and returns whether the snake can move yet.
+    fn run_clock(&mut self, frame_time: Duration) -> bool {
+        let counting_down = self.is_counting_down();
+        if self.countdown_label().is_some() {
+            self.countdown_elapsed += frame_time;
+        }
+        if counting_down {
+            return false;
+        }
+        self.play_time += frame_time;
+        true
+    }
+
+    /// Whether the snake is still waiting for the countdown.
+    fn is_counting_down(&self) -> bool {
+        self.countdown_label().is_some()
+            && self.countdown_label() != COUNTDOWN_LABELS.last().copied()
+    }
+
+    fn tick_duration(&self) -> Duration {
+        Duration::from_millis(
+            u64::try_from(self.current_tick_ms()).unwrap_or(u64::MAX),
+        )
+    }
+
+    /// How far through the current tick we are, from 0 to 1. A crash
+    /// holds everything where the last tick left it.
+    fn tick_progress(&self) -> f32 {
+        if self.dying.is_some() {
+            return 1.;
+        }
+        (self.tick_accumulator.as_secs_f32()
+            / self.tick_duration().as_secs_f32())
+        .min(1.)
+    }
+
+    /// Two vertical walls either side of the starting column. They stop
+    /// short of the edges so no part of the grid is ever walled off, and
+    /// grids too small to fit them get none.
+    fn obstacle_layout(grid: &GridConfig) -> HashSet<Position> {
+        if grid.width < 8 || grid.height < 8 {
+            return HashSet::new();
+        }
+
+        [grid.width / 4, grid.width - 1 - grid.width / 4]
+            .into_iter()
+            .flat_map(|x| {
+                (grid.height / 4..grid.height - grid.height / 4)
+                    .map(move |y| Position { x, y })
+            })
+            .collect()
+    }
+
+    /// Cells nothing is on yet, for setting out a run. The few cells
+    /// straight ahead of each snake are left out so a run never starts with
+    /// one about to hit a wall or go through a portal.
+    fn spare_cells(&self) -> impl Iterator<Item = Position> + '_ {
+        (0..self.grid.height)
+            .flat_map(|y| (0..self.grid.width).map(move |x| Position { x, y }))
+            .filter(move |cell| {
+                let ahead = self.snakes.iter().any(|snake| {
+                    let head = &snake.head_position;
+                    cell.x == head.x
+                        && (head.y - OBSTACLE_CLEARANCE..head.y)
+                            .contains(&cell.y)
+                });
+
+                !ahead
+                    && !self.occupied_by_snake(cell)
+                    && !self.obstacles.contains(cell)
+                    && !self.fruits.iter().any(|fruit| fruit.pos == *cell)
+                    && !self.is_portal(cell)
+            })
+    }
+
+    /// Scatters up to `obstacle_count` walls over spare cells, passing
+    /// over any that would cut something off.
+    fn place_random_obstacles(&mut self) {
+        let mut cells: Vec<Position> = self.spare_cells().collect();
+        cells.shuffle(&mut rand::thread_rng());
+
+        let mut placed = 0;
+        for cell in cells {
+            if placed == self.config.obstacle_count {
+                break;
+            }
+            if self.can_wall_off(cell) {
+                self.obstacles.insert(cell);
+                placed += 1;
+            }
+        }
+    }
+
+    /// Whether a wall on `cell` would still leave every fruit, and every
+    /// free cell next to it, reachable from each snake's head. Checking
+    /// the neighbours is enough to keep the free space in one piece, as
+    /// long as it was before.
+    fn can_wall_off(&self, cell: Position) -> bool {
+        let mut blocked = self.blocked_cells();
+        blocked.push(cell);
+        let around: Vec<Position> = cell
+            .neighbors(self.grid.width, self.grid.height)
+            .into_iter()
+            .flatten()
+            .filter(|next| !blocked.contains(next))
+            .collect();
+
+        self.snakes.iter().all(|snake| {
+            let head = snake.head_position;
+            self.fruits
+                .iter()
+                .map(|fruit| fruit.pos)
+                .chain(around.iter().copied())
+                .all(|goal| {
+                    pathfinding::bfs(
+                        head,
+                        goal,
+                        &blocked,
+                        self.grid.width,
+                        self.grid.height,
+                    )
+                    .is_some()
+                })
+        })
+    }
+
+    /// Pairs up `portal_count` pairs of spare cells as portals.
+    fn place_portals(&mut self) {
+        let mut cells = self.spare_cells().choose_multiple(
+            &mut rand::thread_rng(),
+            self.config.portal_count * 2,
+        );
+        // Cells come back in grid order, which would pair up neighbours.
+        cells.shuffle(&mut rand::thread_rng());
+
+        let mut cells = cells.into_iter();
+        self.portals.clear();
+        while let (Some(a), Some(b)) = (cells.next(), cells.next()) {
+            self.portals.push((a, b));
+        }
+    }
+
+    fn is_portal(&self, cell: &Position) -> bool {
+        self.portals.iter().any(|(a, b)| a == cell || b == cell)
+    }
+
+    /// Where a head arriving on `cell` comes out, if it's a portal. Either
+    /// end leads to the other.
+    fn portal_exit(&self, cell: &Position) -> Option<Position> {
+        self.portals.iter().find_map(|(a, b)| {
+            let exit = if a == cell {
+                b
+            } else if b == cell {
+                a
+            } else {
+                return None;
+            };
+            Some(*exit)
+        })
+    }
+
+    /// Picks a cell uniformly from those in the play area that the snakes,
+    /// walls and anything else on the grid aren't on, or `None` once there
+    /// are none left.
+    fn new_fruit_position(
+        &self,
+        pickups: &[Option<&Position>],
+    ) -> Option<Position> {
+        (0..self.grid.height)
+            .flat_map(|y| (0..self.grid.width).map(move |x| Position { x, y }))
+            .filter(|cell| {
+                self.in_play_area(cell)
+                    && !self.occupied_by_snake(cell)
+                    && !self.obstacles.contains(cell)
+                    && !self.fruits.iter().any(|fruit| fruit.pos == *cell)
+                    && !self.is_portal(cell)
+                    && !pickups.contains(&Some(cell))
+            })
+            .choose(&mut rand::thread_rng())
+    }
+
+    /// Pausing freezes every timer in the scene; on resume they are pushed
+    /// forward by the time spent paused so no ticks are owed.
+    fn toggle_pause(&mut self) {
+        match self.paused_since.take() {
+            Some(since) => {
+                let paused_for = since.elapsed();
+                self.combo_timer += paused_for;
+            }
+            None => self.paused_since = Some(Timestamp::now()),
+        }
+    }
+
+    /// The multiplier the next fruit would build on, or 1 once the combo
+    /// has run out.
+    fn current_combo(&self) -> u32 {
+        let now = self.paused_since.unwrap_or_else(Timestamp::now);
+        if now.duration_since(self.combo_timer).as_millis() < COMBO_WINDOW_MS {
+            self.combo.max(1)
+        } else {
+            1
+        }
+    }
+
+    /// How the run went for the only snake, for the last one standing, or
+    /// for whoever scored more if both went down together.
+    fn results(&self) -> RunResults {
+        let survivors: Vec<&SnakeState> =
+            self.snakes.iter().filter(|snake| !snake.dead).collect();
+        if let [survivor] = survivors[..] {
+            if self.snakes.len() > 1 {
+                return self.results_for(survivor, Some(survivor.player));
+            }
+        }
+
+        self.snakes
+            .iter()
+            .max_by_key(|snake| snake.score)
+            .map_or_else(RunResults::default, |snake| {
+                self.results_for(snake, None)
+            })
+    }
+
+    fn results_for(
+        &self,
+        snake: &SnakeState,
+        winner: Option<Player>,
+    ) -> RunResults {
+        // A snake that crashed never got its last head pushed on.
+        let length = if snake.dead {
+            snake.previous_bodyparts.len()
+        } else {
+            snake.bodyparts.len()
+        };
+        RunResults {
+            score: snake.score,
+            length,
+            fruits_eaten: self.fruits_eaten,
+            duration: self.play_time,
+            won: false,
+            winner,
+        }
+    }
+
+    /// Adds a fruit on a free cell, returning `false` if there is none
+    /// left.
+    fn place_fruit(&mut self) -> bool {
+        // Pickups give up their cell if that's the only one left.
+        let Some(cell) = self
+            .new_fruit_position(&[
+                self.poison_fruit.as_ref(),
+                self.speedboost.as_ref(),
+            ])
+            .or_else(|| self.poison_fruit.take())
+            .or_else(|| self.speedboost.take())
+        else {
+            return false;
+        };
+        self.fruits
+            .push(Fruit::random(cell, self.current_tick_ms()));
+        true
+    }
+
+    fn place_poison_fruit(&mut self) {
+        self.poison_fruit =
+            self.new_fruit_position(&[self.speedboost.as_ref()]);
+        self.poison_lifetime = POISON_FRUIT_LIFETIME;
+    }
+
+    /// Eats the speed boost if a head is on it, and otherwise counts down
+    /// the boost, the item on the grid and the wait for the next one.
+    fn tick_speedboost(&mut self) {
+        if self.snakes.iter().any(|snake| {
+            !snake.dead
+                && self.speedboost.as_ref() == Some(&snake.head_position)
+        }) {
+            self.speedboost = None;
+            self.boost_ticks_remaining = SPEEDBOOST_TICKS;
+            return;
+        }
+
+        self.boost_ticks_remaining =
+            self.boost_ticks_remaining.saturating_sub(1);
+        if self.speedboost.is_some() {
+            self.speedboost_lifetime =
+                self.speedboost_lifetime.saturating_sub(1);
+            if self.speedboost_lifetime == 0 {
+                self.speedboost = None;
+            }
+        } else if self.boost_ticks_remaining == 0 {
+            self.ticks_until_speedboost =
+                self.ticks_until_speedboost.saturating_sub(1);
+            if self.ticks_until_speedboost == 0 {
+                self.place_speedboost();
+            }
+        }
+    }
+
+    fn place_speedboost(&mut self) {
+        self.speedboost =
+            self.new_fruit_position(&[self.poison_fruit.as_ref()]);
+        self.speedboost_lifetime = SPEEDBOOST_LIFETIME;
+        self.ticks_until_speedboost = SPEEDBOOST_INTERVAL_TICKS;
+    }
+
+    /// Keys steer each snake from its own side of the keyboard. Swipes and
+    /// the mouse, when it's switched on, only steer player one.
+    fn handle_input(&mut self) {
+        let two_player = self.snakes.len() > 1;
+        for snake in &mut self.snakes {
+            let input_map = if two_player {
+                snake.player.input_map()
+            } else {
+                Some(self.config.input_map.clone())
+            };
+            let Some(input_map) = input_map else {
+                continue;
+            };
+            for direction in input_map.pressed() {
+                snake.queue_direction(direction);
+            }
+        }
+
+        // Touches also move the mouse, which would steer towards the
+        // finger instead of following the swipe.
+        let touches = touches();
+        self.handle_swipes(&touches);
+        if touches.is_empty() && is_mouse_button_down(MouseButton::Left) {
+            self.steer_with_mouse(&self.mouse_cell());
+        }
+    }
+
+    /// Turns player one towards `target`, if mouse steering is on.
+    fn steer_with_mouse(&mut self, target: &Position) {
+        if !self.config.mouse_steering {
+            return;
+        }
+        if let Some(snake) = self.snakes.first_mut() {
+            snake.steer_towards(target);
+        }
+    }
+
+    /// Turns the way a finger moved furthest between touching down and
+    /// lifting off, as long as it moved far enough to count as a swipe.
+    fn handle_swipes(&mut self, touches: &[Touch]) {
+        for touch in touches {
+            match touch.phase {
+                TouchPhase::Started => {
+                    self.touch_starts.insert(touch.id, touch.position);
+                }
+                TouchPhase::Ended => {
+                    let Some(start) = self.touch_starts.remove(&touch.id)
+                    else {
+                        continue;
+                    };
+                    let delta = touch.position - start;
+                    if delta.length() < SWIPE_MIN_DISTANCE {
+                        continue;
+                    }
+
+                    let direction = if delta.x.abs() >= delta.y.abs() {
+                        if delta.x < 0. {
+                            Direction::Left
+                        } else {
+                            Direction::Right
+                        }
+                    } else if delta.y < 0. {
+                        Direction::Up
+                    } else {
+                        Direction::Down
+                    };
+                    if let Some(snake) = self.snakes.first_mut() {
+                        snake.queue_direction(direction);
+                    }
+                }
+                TouchPhase::Cancelled => {
+                    self.touch_starts.remove(&touch.id);
+                }
+                TouchPhase::Stationary | TouchPhase::Moved => {}
+            }
+        }
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    fn mouse_cell(&self) -> Position {
+        let (mx, my) = mouse_position();
+
+        Position {
+            x: (mx / (screen_width() / self.grid.width as f32)) as i32,
+            y: (my / (screen_height() / self.grid.height as f32)) as i32,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum GameError {
+    NoActiveScene,
+    /// A scene was still borrowed when `Game` needed it.
+    BorrowFailed,
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoActiveScene => write!(f, "No scene is active"),
+            Self::BorrowFailed => write!(f, "A scene is already in use"),
+        }
+    }
+}
+
+impl From<BorrowError> for GameError {
+    fn from(_: BorrowError) -> Self {
+        Self::BorrowFailed
+    }
+}
+
+impl From<BorrowMutError> for GameError {
+    fn from(_: BorrowMutError) -> Self {
+        Self::BorrowFailed
+    }
+}
+
+struct Game {
+    renderer: Renderer,
+    high_score: HighScore,
+    fullscreen: bool,
+    /// The window size to go back to when leaving fullscreen.
+    windowed_size: (f32, f32),
+    scenes: Vec<Rc<RefCell<dyn Scene>>>,
+    active_scene: Option<Rc<RefCell<dyn Scene>>>,
+}
+
+impl Game {
+    fn new(config: &Config, theme: Rc<Cell<Theme>>) -> Self {
+        Self {
+            renderer: Renderer::new(
+                &config.grid,
+                config.checkerboard,
+                theme,
+                config.reduce_motion,
+            ),
+            high_score: HighScore::load(),
+            fullscreen: config.fullscreen,
+            windowed_size: (
+                f32::from(config.screen_width),
+                f32::from(config.screen_height),
+            ),
+            scenes: Vec::new(),
+            active_scene: None,
+        }
+    }
+
+    fn update(&mut self) -> Result<(), GameError> {
+        // Keys that work the same on every scene.
+        if is_key_pressed(KeyCode::F11) {
+            self.toggle_fullscreen();
+        }
+        if is_key_pressed(KeyCode::F2) {
+            self.renderer.show_grid = !self.renderer.show_grid;
+            let show_grid = self.renderer.show_grid;
+            Self::save_pref(|prefs| prefs.checkerboard = Some(show_grid));
+        }
+
+        let swap = self
+            .active_scene
+            .as_ref()
+            .ok_or(GameError::NoActiveScene)?
+            .try_borrow_mut()?
+            .update();
+
+        if let Some(s) = swap {
+            match s {
+                SwapScene::StartMenu => self.set_scene(0)?,
+                SwapScene::Settings => self.set_scene(3)?,
+                SwapScene::HowToPlay => self.set_scene(4)?,
+                SwapScene::Game(config) => {
+                    self.renderer = Renderer::new(
+                        &config.grid,
+                        self.renderer.show_grid,
+                        Rc::clone(&self.renderer.theme),
+                        self.renderer.reduce_motion,
+                    );
+                    self.scenes[1].try_borrow_mut()?.set_config(&config);
+                    self.set_scene(1)?;
+                }
+                SwapScene::GameOver(results) => {
+                    let new_record =
+                        self.high_score.maybe_update(results.score);
+                    if new_record {
+                        if let Err(e) = self.high_score.save() {
+                            eprintln!("Failed to save high score: {e}");
+                        }
+                    }
+
+                    self.scenes[2]
+                        .try_borrow_mut()?
+                        .set_results(&results, new_record);
+                    self.set_scene(2)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Switches between fullscreen and a window the size it was before,
+    /// and remembers the choice for next time.
+    fn toggle_fullscreen(&mut self) {
+        self.fullscreen = !self.fullscreen;
+        if self.fullscreen {
+            self.windowed_size = (screen_width(), screen_height());
+            set_fullscreen(true);
+        } else {
+            set_fullscreen(false);
+            let (width, height) = self.windowed_size;
+            request_new_screen_size(width, height);
+        }
+
+        let fullscreen = self.fullscreen;
+        Self::save_pref(|prefs| prefs.fullscreen = Some(fullscreen));
+    }
+
+    /// Writes a single change into the preferences file, keeping the rest.
+    fn save_pref(change: impl FnOnce(&mut UserPrefs)) {
+        let mut prefs = UserPrefs::load();
+        change(&mut prefs);
+        if let Err(e) = prefs.save() {
+            eprintln!("Failed to save settings: {e}");
+        }
+    }
+
+    fn draw(&self) -> Result<(), GameError> {
+        let scene = self
+            .active_scene
+            .as_ref()
+            .ok_or(GameError::NoActiveScene)?
+            .try_borrow()?;
+        scene.draw_background(&self.renderer);
+        scene.draw(&self.renderer, &self.high_score);
+        Ok(())
+    }
+
+    fn add_scene(&mut self, scene: Rc<RefCell<dyn Scene>>) {
+        self.scenes.push(scene);
+    }
+
+    fn set_scene(&mut self, index: usize) -> Result<(), GameError> {
+        if let Some(scene) = &self.active_scene {
+            scene.try_borrow_mut()?.on_exit();
+        }
+        let scene = Rc::clone(&self.scenes[index]);
+        scene.try_borrow_mut()?.on_enter();
+        self.active_scene = Some(scene);
+        Ok(())
+    }
+
+    /// Swaps whatever is on screen for an error message. The failed scene
+    /// may still be borrowed, so it is dropped without `on_exit`.
+    fn show_error(&mut self, error: &GameError) {
+        eprintln!("Fatal error: {error}");
+        self.active_scene = Some(Rc::new(RefCell::new(ErrorScene::new(error))));
+    }
+}
+
+/// Draws the game. Cell sizes are worked out from the window every time
+/// they're needed, so the grid keeps filling the window when it's resized.
+struct Renderer {
+    grid: GridConfig,
+    /// A pixel per cell, stretched over the window by `draw_grid`.
+    checkerboard: Texture2D,
+    show_grid: bool,
+    /// Shared with the settings, which can swap it at any time.
+    theme: Rc<Cell<Theme>>,
+    /// Hold the background still and play crashes without flashing.
+    reduce_motion: bool,
+}
+
+impl Renderer {
+    fn new(
+        grid: &GridConfig,
+        show_grid: bool,
+        theme: Rc<Cell<Theme>>,
+        reduce_motion: bool,
+    ) -> Self {
+        Self {
+            grid: *grid,
+            checkerboard: Self::checkerboard(grid),
+            show_grid,
+            theme,
+            reduce_motion,
+        }
+    }
+
+    /// Alternate cells are white and the rest clear, so the texture can be
+    /// tinted to whatever the theme wants.
+    fn checkerboard(grid: &GridConfig) -> Texture2D {
+        let width = u16::try_from(grid.width).unwrap_or(u16::MAX);
+        let height = u16::try_from(grid.height).unwrap_or(u16::MAX);
+        let mut image =
+            Image::gen_image_color(width, height, Color::new(0., 0., 0., 0.));
+        for y in 0..u32::from(height) {
+            for x in 0..u32::from(width) {
+                if (x + y) % 2 == 1 {
+                    image.set_pixel(x, y, WHITE);
+                }
+            }
+        }
+
+        let texture = Texture2D::from_image(&image);
+        // Keeps the cells sharp instead of blurring into each other.
+        texture.set_filter(FilterMode::Nearest);
+        texture
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn cell_width(&self) -> f32 {
+        screen_width() / self.grid.width as f32
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn cell_height(&self) -> f32 {
+        screen_height() / self.grid.height as f32
+    }
+
+    fn object_gap_width(&self) -> f32 {
+        self.cell_width() * 0.1
+    }
+
+    fn object_gap_height(&self) -> f32 {
+        self.cell_height() * 0.1
+    }
+
+    fn object_width(&self) -> f32 {
+        self.cell_width() - self.object_gap_width()
+    }
+
+    fn object_height(&self) -> f32 {
+        self.cell_height() - self.object_gap_height()
+    }
+
+    fn theme(&self) -> Theme {
+        self.theme.get()
+    }
+
+    fn clear(&self) {
+        clear_background(self.theme().background);
+    }
+
+    /// Shades alternate cells so distances are easier to judge, unless
+    /// it's been switched off.
+    fn draw_grid(&self) {
+        if !self.show_grid {
+            return;
+        }
+        let [even, odd] = self.theme().checker;
+        draw_rectangle(0., 0., screen_width(), screen_height(), even);
+        draw_texture_ex(
+            &self.checkerboard,
+            0.,
+            0.,
+            odd,
+            DrawTextureParams {
+                dest_size: Some(vec2(screen_width(), screen_height())),
+                ..Default::default()
+            },
+        );
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn draw_starfield(&self, starfield: &Starfield) {
+        let time = if self.reduce_motion {
+            0.
+        } else {
+            get_time() as f32
+        };
+        let text = self.theme().text;
+
+        for star in &starfield.stars {
+            let (speed, size, color) = if star.near {
+                (12., 2., text)
+            } else {
+                (4., 1., Color { a: 0.6, ..text })
+            };
+            let x = star
+                .x
+                .mul_add(screen_width(), speed * time)
+                .rem_euclid(screen_width());
+
+            draw_rectangle(x, star.y * screen_height(), size, size, color);
+        }
+    }
+
+    fn snake_colors(&self, player: Player) -> SnakeColors {
+        let [one, two, computer] = self.theme().snakes;
+        match player {
+            Player::One => one,
+            Player::Two => two,
+            Player::Computer => computer,
+        }
+    }
+
+    /// Fades from the body colour to the tail colour along the snake, with
+    /// `index` counting segments from the head out of `length`.
+    #[allow(clippy::cast_precision_loss)]
+    fn draw_bodypart(
+        &self,
+        player: Player,
+        index: usize,
+        length: usize,
+        from: &Position,
+        to: &Position,
+        t: f32,
+    ) {
+        let colors = self.snake_colors(player);
+        let (near, far) = (colors.body, colors.tail);
+        let fade = index as f32 / length.max(1) as f32;
+        let color = Color::new(
+            (far.r - near.r).mul_add(fade, near.r),
+            (far.g - near.g).mul_add(fade, near.g),
+            (far.b - near.b).mul_add(fade, near.b),
+            1.,
+        );
+        self.draw_rect_interpolated(from, to, t, color);
+    }
+
+    /// Draws the head with a pair of eyes on the edge it's moving
+    /// towards.
+    fn draw_head(
+        &self,
+        player: Player,
+        direction: &Direction,
+        from: &Position,
+        to: &Position,
+        t: f32,
+    ) {
+        self.draw_rect_interpolated(
+            from,
+            to,
+            t,
+            self.snake_colors(player).head,
+        );
+
+        let (x, y) = Self::interpolate(from, to, t);
+        let (cell_width, cell_height) = (self.cell_width(), self.cell_height());
+        let centre_x = (x + 0.5) * cell_width;
+        let centre_y = (y + 0.5) * cell_height;
+        // Forwards and sideways as fractions of a cell.
+        let (forward_x, forward_y) = match direction {
+            Direction::Up => (0., -1.),
+            Direction::Left => (-1., 0.),
+            Direction::Down => (0., 1.),
+            Direction::Right => (1., 0.),
+        };
+        let (side_x, side_y) = (forward_y, forward_x);
+        let radius = self.object_width().min(self.object_height()) * 0.1;
+
+        for side in [-1., 1.] {
+            draw_circle(
+                centre_x + (forward_x * 0.2 + side_x * side * 0.2) * cell_width,
+                centre_y
+                    + (forward_y * 0.2 + side_y * side * 0.2) * cell_height,
+                radius,
+                self.theme().eyes,
+            );
+        }
+    }
+
+    fn draw_fruit(&self, fruit: &Fruit) {
+        // Blink on alternate ticks as a golden fruit is about to expire.
+        if fruit.golden_ticks_left.is_some_and(|ticks_left| {
+            ticks_left <= GOLDEN_FRUIT_BLINK_TICKS && ticks_left % 2 == 1
+        }) {
+            return;
+        }
+        self.draw_rect_at_point(&fruit.pos, self.fruit_color(fruit));
+    }
+
+    fn fruit_color(&self, fruit: &Fruit) -> Color {
+        let theme = self.theme();
+        if fruit.golden_ticks_left.is_some() {
+            return theme.golden_fruit;
+        }
+        let [apple, berry, melon] = theme.fruits;
+        match fruit.kind {
+            FruitKind::Apple => apple,
+            FruitKind::Berry => berry,
+            FruitKind::Melon => melon,
+        }
+    }
+
+    /// Faint dots along a path, smaller than anything you can run into.
+    #[allow(clippy::cast_precision_loss)]
+    fn draw_hint(&self, path: &[Position]) {
+        for p in path {
+            draw_circle(
+                (p.x as f32 + 0.5) * self.cell_width(),
+                (p.y as f32 + 0.5) * self.cell_height(),
+                self.object_width().min(self.object_height()) / 8.,
+                Color {
+                    a: 0.3,
+                    ..self.theme().text
+                },
+            );
+        }
+    }
+
+    /// Drawn round rather than square so it can't be mistaken for food.
+    #[allow(clippy::cast_precision_loss)]
+    fn draw_poison_fruit(&self, p: &Position) {
+        draw_circle(
+            (p.x as f32 + 0.5) * self.cell_width(),
+            (p.y as f32 + 0.5) * self.cell_height(),
+            self.object_width().min(self.object_height()) / 2.,
+            self.theme().poison_fruit,
+        );
+    }
+
+    /// A small diamond, so it stands apart from the fruit.
+    #[allow(clippy::cast_precision_loss)]
+    fn draw_speedboost(&self, p: &Position) {
+        let center = Vec2::new(
+            (p.x as f32 + 0.5) * self.cell_width(),
+            (p.y as f32 + 0.5) * self.cell_height(),
+        );
+        let half_width = self.object_width() / 2.;
+        let half_height = self.object_height() / 2.;
+        let top = center - Vec2::new(0., half_height);
+        let bottom = center + Vec2::new(0., half_height);
+
+        let color = self.theme().speedboost;
+        draw_triangle(top, center - Vec2::new(half_width, 0.), bottom, color);
+        draw_triangle(top, center + Vec2::new(half_width, 0.), bottom, color);
+    }
+
+    /// Both ends of a portal, joined by a faint line so it's clear which
+    /// goes where.
+    #[allow(clippy::cast_precision_loss)]
+    fn draw_portal_pair(&self, a: &Position, b: &Position) {
+        let center = |p: &Position| {
+            (
+                (p.x as f32 + 0.5) * self.cell_width(),
+                (p.y as f32 + 0.5) * self.cell_height(),
+            )
+        };
+        let ((ax, ay), (bx, by)) = (center(a), center(b));
+        let color = self.theme().portal;
+        draw_line(ax, ay, bx, by, 2., Color { a: 0.3, ..color });
+
+        self.draw_rect_at_point(a, color);
+        self.draw_rect_at_point(b, color);
+    }
+
+    /// Shades everything outside the play area and outlines its edge.
+    #[allow(clippy::cast_precision_loss)]
+    fn draw_inactive_zone(
+        &self,
+        grid: &GridConfig,
+        origin: &Position,
+        width: i32,
+        height: i32,
+    ) {
+        let (grid_width, grid_height) = (
+            grid.width as f32 * self.cell_width(),
+            grid.height as f32 * self.cell_height(),
+        );
+        let left = origin.x as f32 * self.cell_width();
+        let top = origin.y as f32 * self.cell_height();
+        let right = (origin.x + width) as f32 * self.cell_width();
+        let bottom = (origin.y + height) as f32 * self.cell_height();
+        let theme = self.theme();
+        let shade = Color {
+            a: 0.6,
+            ..theme.background
+        };
+
+        draw_rectangle(0., 0., grid_width, top, shade);
+        draw_rectangle(0., bottom, grid_width, grid_height - bottom, shade);
+        draw_rectangle(0., top, left, bottom - top, shade);
+        draw_rectangle(right, top, grid_width - right, bottom - top, shade);
+        draw_rectangle_lines(
+            left,
+            top,
+            right - left,
+            bottom - top,
+            2.,
+            theme.danger,
+        );
+    }
+
+    fn draw_obstacle(&self, p: &Position) {
+        self.draw_rect_at_point(p, self.theme().obstacle);
+    }
+
+    fn draw_crashed_segment(&self, p: &Position) {
+        self.draw_rect_at_point(p, self.theme().danger);
+    }
+
+    /// Rings the cell a snake crashed into.
+    #[allow(clippy::cast_precision_loss)]
+    fn draw_crash_site(&self, p: &Position) {
+        let (cell_width, cell_height) = (self.cell_width(), self.cell_height());
+        draw_rectangle_lines(
+            p.x as f32 * cell_width,
+            p.y as f32 * cell_height,
+            cell_width,
+            cell_height,
+            4.,
+            self.theme().danger,
+        );
+    }
+
+    fn draw_steering_target(&self, target: &Position) {
+        self.draw_rect_at_point(
+            target,
+            Color {
+                a: 0.15,
+                ..self.theme().text
+            },
+        );
+    }
+
+    fn draw_fruit_telegraph(&self, fruit: &Fruit) {
+        let color = self.fruit_color(fruit);
+        self.draw_rect_at_point(&fruit.pos, Color { a: 0.25, ..color });
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn draw_rect_at_point(&self, p: &Position, c: Color) {
+        let real_x = p.x as f32 * self.cell_width();
+        let real_y = p.y as f32 * self.cell_height();
+
+        let real_x = real_x + self.object_gap_width() / 2.;
+        let real_y = real_y + self.object_gap_height() / 2.;
+
+        self.draw_object(real_x, real_y, c);
+    }
+
+    /// Fills an object-sized rect, outlined if the theme says so.
+    fn draw_object(&self, x: f32, y: f32, c: Color) {
+        let (width, height) = (self.object_width(), self.object_height());
+        draw_rectangle(x, y, width, height, c);
+        if let Some(outline) = self.theme().outline {
+            draw_rectangle_lines(x, y, width, height, 2., outline);
+        }
+    }
+
+    /// The cell `t` of the way from `from` to `to`, in fractions of a
+    /// cell. Cells that aren't next to each other, like when wrapping round
+    /// the edge, are jumped between rather than slid across the whole grid.
+    #[allow(clippy::cast_precision_loss)]
+    fn interpolate(from: &Position, to: &Position, t: f32) -> (f32, f32) {
+        let t = if from.manhattan_distance(to) > 1 {
+            1.
+        } else {
+            t
+        };
+        (
+            ((to.x - from.x) as f32).mul_add(t, from.x as f32),
+            ((to.y - from.y) as f32).mul_add(t, from.y as f32),
+        )
+    }
+
+    /// Draws a rect `t` of the way from `from` to `to`.
+    fn draw_rect_interpolated(
+        &self,
+        from: &Position,
+        to: &Position,
+        t: f32,
+        c: Color,
+    ) {
+        let (x, y) = Self::interpolate(from, to, t);
+
+        self.draw_object(
+            x * self.cell_width() + self.object_gap_width() / 2.,
+            y * self.cell_height() + self.object_gap_height() / 2.,
+            c,
+        );
+    }
+
+    /// Centres text across the window, with `y` and the size in
+    /// `MenuLayout` units.
+    #[allow(clippy::unused_self)]
+    fn draw_text_centered(
+        &self,
+        text: &str,
+        y: f32,
+        font_size: u16,
+        color: Color,
+    ) {
+        let layout = MenuLayout::current();
+        let size = measure_text(text, None, font_size, layout.scale);
+        let x = (screen_width() - size.width) / 2.;
+
+        draw_text(
+            text,
+            x,
+            layout.y(y),
+            layout.size(f32::from(font_size)),
+            color,
+        );
+    }
+
+    fn draw_pause_overlay(&self) {
+        let theme = self.theme();
+        draw_rectangle(
+            0.,
+            0.,
+            screen_width(),
+            screen_height(),
+            Color {
+                a: 0.5,
+                ..theme.background
+            },
+        );
+        self.draw_text_centered(
+            "PAUSED",
+            MenuLayout::SIZE / 2.,
+            80,
+            theme.text,
+        );
+    }
+
+    fn draw_countdown(&self, label: &str) {
+        let text = self.theme().text;
+        self.draw_text_centered(label, MenuLayout::SIZE / 2., 160, text);
+    }
+
+    /// Scores down the top left, one line per player when there are two,
+    /// with the combo under them.
+    fn draw_hud(&self, snakes: &[SnakeState], combo: u32) {
+        let theme = self.theme();
+        let mut y = 30.;
+        for snake in snakes {
+            let label = if snakes.len() > 1 {
+                format!("{}: {}", snake.player.name(), snake.score)
+            } else {
+                format!("Score: {}", snake.score)
+            };
+            draw_text(&label, 10., y, 30., theme.text);
+            y += 30.;
+        }
+        if combo > 1 {
+            draw_text(&format!("Combo x{combo}"), 10., y, 30., theme.highlight);
+        }
+    }
+
+    fn draw_time_left(&self, time_left: Duration) {
+        let theme = self.theme();
+        // Rounded up, so the clock only shows 0:00 once time is up.
+        let seconds =
+            time_left.as_secs() + u64::from(time_left.subsec_nanos() > 0);
+        let color = if seconds <= 10 {
+            theme.danger
+        } else {
+            theme.text
+        };
+        let text = format!("{}:{:02}", seconds / 60, seconds % 60);
+        let size = measure_text(&text, None, 30, 1.);
+
+        draw_text(&text, screen_width() - size.width - 10., 30., 30., color);
+    }
+
+    /// Fruit eaten against the quota, over a bar of the ticks left to eat
+    /// them in.
+    #[allow(clippy::cast_precision_loss)]
+    fn draw_quota_meter(
+        &self,
+        eaten: u32,
+        needed: u32,
+        ticks_left: u32,
+        window_ticks: u32,
+    ) {
+        const WIDTH: f32 = 200.;
+        let x = screen_width() - WIDTH - 10.;
+        let theme = self.theme();
+        let color = if eaten >= needed {
+            theme.good
+        } else {
+            theme.warning
+        };
+
+        draw_text(&format!("Quota: {eaten}/{needed}"), x, 30., 30., color);
+        draw_rectangle(
+            x,
+            40.,
+            WIDTH * ticks_left as f32 / window_ticks.max(1) as f32,
+            10.,
+            color,
+        );
+        draw_rectangle_lines(x, 40., WIDTH, 10., 2., theme.text);
+    }
+
+    fn draw_button<S>(&self, but: &Button<S>, is_focused: bool) {
+        if is_focused {
+            let rect = but.rect();
+            let border = MenuLayout::current().size(4.);
+            draw_rectangle(
+                rect.x - border,
+                rect.y - border,
+                rect.w + border * 2.,
+                rect.h + border * 2.,
+                self.theme().focus,
+            );
+        }
+
+        let hovered = but.is_mouse_over_button();
+        self.draw_button_stateful(but, hovered, hovered && but.armed);
+    }
+
+    /// Draws the button itself, shaded by whether the mouse is over it and
+    /// whether a click on it is being held.
+    fn draw_button_stateful<S>(
+        &self,
+        but: &Button<S>,
+        hovered: bool,
+        pressed: bool,
+    ) {
+        let theme = self.theme();
+        let fill = if pressed {
+            theme.button_pressed
+        } else if hovered {
+            theme.button_hovered
+        } else {
+            theme.button
+        };
+        let layout = MenuLayout::current();
+        let rect = but.rect();
+        draw_rectangle(rect.x, rect.y, rect.w, rect.h, fill);
+        if let Some(outline) = theme.outline {
+            draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 2., outline);
+        }
+
+        draw_text(
+            &but.label,
+            rect.x,
+            rect.y + rect.h / 2. + layout.size(12.),
+            layout.size(50.),
+            theme.button_text,
+        );
+
+        if let Some(shortcut) = but.shortcut_label() {
+            draw_text(
+                &shortcut,
+                rect.x + layout.size(6.),
+                rect.y + layout.size(20.),
+                layout.size(20.),
+                theme.button_shortcut,
+            );
+        }
+    }
+}
+
+/// 16x16 pixel art for the window icon: `#` is body, `@` is the head,
+/// `o` is the fruit and anything else is transparent.
+const ICON_PIXELS: [&str; 16] = [
+    "................",
+    "..........oo....",
+    ".........oooo...",
+    ".........oooo...",
+    "..........oo....",
+    "................",
+    "..#######@......",
+    "..#.............",
+    "..#.............",
+    "..########......",
+    ".........#......",
+    ".........#......",
+    "..########......",
+    "................",
+    "................",
+    "................",
+];
+
+fn icon_image<const N: usize>() -> [u8; N] {
+    let size = (N / 4).isqrt();
+    let scale = size / ICON_PIXELS.len();
+    let mut image = [0; N];
+
+    for (i, pixel) in image.chunks_exact_mut(4).enumerate() {
+        let (x, y) = ((i % size) / scale, (i / size) / scale);
+        let colour: [u8; 4] = match ICON_PIXELS[y].as_bytes()[x] {
+            b'#' => [0, 228, 48, 255],
+            b'@' => [204, 255, 204, 255],
+            b'o' => [230, 41, 55, 255],
+            _ => [0, 0, 0, 0],
+        };
+        pixel.copy_from_slice(&colour);
+    }
+    image
+}
 
 fn get_conf() -> macroquad::window::Conf {
+    let config = Config::load();
+
     macroquad::window::Conf {
         window_title: "Snek :þ".to_owned(),
-        window_width: 800,
-        window_height: 800,
+        window_width: i32::from(config.screen_width),
+        window_height: i32::from(config.screen_height),
+        fullscreen: config.fullscreen,
+        icon: Some(Icon {
+            small: icon_image(),
+            medium: icon_image(),
+            big: icon_image(),
+        }),
         ..Default::default()
     }
 }
 
+/// Browsers don't let a page close itself, so there the exit buttons go
+/// back to the menu instead.
+#[cfg(not(target_arch = "wasm32"))]
+const EXIT_LABEL: &str = "Exit Game";
+#[cfg(target_arch = "wasm32")]
+const EXIT_LABEL: &str = "Back to Menu";
+
+fn quit<S>(_: &mut S) -> Option<SwapScene> {
+    if cfg!(target_arch = "wasm32") {
+        Some(SwapScene::StartMenu)
+    } else {
+        std::process::exit(0)
+    }
+}
+
+/// `rand` has no source of randomness of its own on the web, so it gets
+/// one from macroquad, seeded from the clock at startup.
+#[cfg(target_arch = "wasm32")]
+fn web_random(buf: &mut [u8]) -> Result<(), getrandom::Error> {
+    for byte in buf {
+        *byte = macroquad::rand::rand().to_le_bytes()[0];
+    }
+    Ok(())
+}
+
+#[cfg(target_arch = "wasm32")]
+getrandom::register_custom_getrandom!(web_random);
+
 #[macroquad::main(get_conf)]
 async fn main() {
-    let mut game = Game::new();
+    #[cfg(target_arch = "wasm32")]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    macroquad::rand::srand(macroquad::miniquad::date::now() as u64);
+
+    let config = Config::load();
+    let theme = Rc::new(Cell::new(config.theme));
+    let mut game = Game::new(config, Rc::clone(&theme));
+
+    let levels: Vec<Rc<Level>> = Level::bundled(&config.grid)
+        .into_iter()
+        .map(Rc::new)
+        .collect();
 
-    let mainmenu = Rc::new(RefCell::new(Menu::new()));
+    let mut game_config = config.game_config();
+    game_config.level = config.level.as_ref().and_then(|name| {
+        levels.iter().find(|level| &level.name == name).cloned()
+    });
+    let game_config = Rc::new(RefCell::new(game_config));
 
-    let gamescene = Rc::new(RefCell::new(GameScene::new()));
+    let mainmenu = Rc::new(RefCell::new(Menu::new(
+        Rc::clone(&game_config),
+        config.last_run,
+    )));
+
+    let gamescene = Rc::new(RefCell::new(GameScene::new(config)));
 
     let game_over = Rc::new(RefCell::new(GameOver::new()));
 
+    let settings =
+        Rc::new(RefCell::new(Settings::new(game_config, levels, theme)));
+
     game.add_scene(mainmenu);
 
     game.add_scene(gamescene);
 
     game.add_scene(game_over);
 
-    game.set_scene(0);
+    game.add_scene(settings);
+
+    game.add_scene(Rc::new(RefCell::new(HowToPlay::new())));
+
+    if let Err(e) = game.set_scene(0) {
+        game.show_error(&e);
+    }
 
     loop {
-        game.update();
-        game.draw();
+        if let Err(e) = game.update().and_then(|()| game.draw()) {
+            game.show_error(&e);
+        }
         next_frame().await;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A one player run on the default grid with nothing on it but the
+    /// snake, its head in the middle and facing up.
+    fn empty_scene() -> GameScene {
+        scene_on(GridConfig::default())
+    }
+
+    fn scene_on(grid: GridConfig) -> GameScene {
+        let config = Config::from_prefs(&UserPrefs::default());
+        let mut scene = GameScene::new(&config);
+        scene.set_config(&GameConfig {
+            grid,
+            ..GameConfig::default()
+        });
+        scene.on_enter();
+        scene.fruits.clear();
+        scene.obstacles.clear();
+        scene.portals.clear();
+        scene
+    }
+
+    /// Ticks of `Easy`, `Medium` and `Hard` speed, with a fruit
+    /// telegraphed for 2, 2 and 4 of them.
+    const TICK_SPEEDS: [u128; 3] = [350, BASE_TICK_MS, 150];
+
+    /// Puts a fresh fruit `cells` ahead of the head and runs ticks of
+    /// `tick_ms` until the head gets there, returning the scene
+    /// afterwards.
+    fn arrive_after(tick_ms: u128, cells: u32) -> GameScene {
+        let mut scene = empty_scene();
+        scene.config.tick_speed_ms = tick_ms;
+        let head = scene.snakes[0].head_position;
+        let fruit = Position {
+            x: head.x,
+            y: head.y - i32::try_from(cells).unwrap(),
+        };
+        scene.fruits.push(Fruit {
+            golden_ticks_left: None,
+            ..Fruit::random(fruit, scene.current_tick_ms())
+        });
+        for _ in 0..cells {
+            assert!(scene.step().is_none());
+        }
+        assert_eq!(scene.snakes[0].head_position, fruit);
+        scene
+    }
+
+    /// A run started on `config` with everything it set out left in place.
+    fn scene_with(config: GameConfig) -> GameScene {
+        let mut scene =
+            GameScene::new(&Config::from_prefs(&UserPrefs::default()));
+        scene.set_config(&config);
+        scene.on_enter();
+        scene
+    }
+
+    #[test]
+    fn random_walls_need_walls_on_and_no_level() {
+        let walls_off = scene_with(GameConfig {
+            obstacle_count: 5,
+            ..GameConfig::default()
+        });
+        assert!(walls_off.obstacles.is_empty());
+
+        let grid = GridConfig::default();
+        let maze = Level::bundled(&grid)
+            .into_iter()
+            .find(|level| level.name == "Maze")
+            .unwrap();
+        let walls = maze.walls.clone();
+        let on_level = scene_with(GameConfig {
+            obstacles: true,
+            obstacle_count: 5,
+            level: Some(Rc::new(maze)),
+            ..GameConfig::default()
+        });
+        assert!(on_level.obstacles.is_subset(&walls));
+    }
+
+    #[test]
+    fn random_walls_leave_every_free_cell_reachable() {
+        let grid = GridConfig::new(8, 8).unwrap();
+        for _ in 0..20 {
+            let scene = scene_with(GameConfig {
+                grid,
+                obstacles: true,
+                obstacle_count: 30,
+                fruit_count: 3,
+                ..GameConfig::default()
+            });
+            assert!(
+                scene.obstacles.len() > GameScene::obstacle_layout(&grid).len()
+            );
+            let head = scene.snakes[0].head_position;
+            let blocked = scene.blocked_cells();
+
+            for y in 0..8 {
+                for x in 0..8 {
+                    let cell = Position { x, y };
+                    if cell != head && !blocked.contains(&cell) {
+                        assert!(
+                            pathfinding::bfs(head, cell, &blocked, 8, 8)
+                                .is_some(),
+                            "{cell:?} is walled off"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mouse_only_steers_when_switched_on() {
+        let mut scene = empty_scene();
+        let head = scene.snakes[0].head_position;
+        let target = Position {
+            x: head.x + 5,
+            y: head.y,
+        };
+
+        scene.steer_with_mouse(&target);
+        assert!(scene.snakes[0].next_direction.is_empty());
+
+        scene.config.mouse_steering = true;
+        scene.steer_with_mouse(&target);
+        assert_eq!(
+            scene.snakes[0].next_direction.back(),
+            Some(&Direction::Right)
+        );
+    }
+
+    #[test]
+    fn cycle_wraps_round_and_restarts_from_unknown_entries() {
+        assert_eq!(cycle(&[1, 2, 3], &1), 2);
+        assert_eq!(cycle(&[1, 2, 3], &3), 1);
+        assert_eq!(cycle(&[1, 2, 3], &7), 1);
+    }
+
+    #[test]
+    fn settings_only_save_a_grid_picked_there() {
+        let config = Rc::new(RefCell::new(GameConfig {
+            // As if from `--grid 50x50`.
+            grid: GridConfig::new(50, 50).unwrap(),
+            ..GameConfig::default()
+        }));
+        let mut settings = Settings::new(
+            config,
+            Vec::new(),
+            Rc::new(Cell::new(Theme::default())),
+        );
+
+        let mut prefs = UserPrefs {
+            grid_width: Some(30),
+            grid_height: Some(30),
+            ..UserPrefs::default()
+        };
+        settings.update_prefs(&mut prefs);
+        assert_eq!((prefs.grid_width, prefs.grid_height), (Some(30), Some(30)));
+
+        settings.next_grid();
+        settings.update_prefs(&mut prefs);
+        assert_eq!((prefs.grid_width, prefs.grid_height), (Some(15), Some(15)));
+    }
+
+    #[test]
+    fn play_time_leaves_out_the_countdown() {
+        let mut scene = empty_scene();
+        let step =
+            Duration::from_millis(u64::try_from(COUNTDOWN_STEP_MS).unwrap());
+        for _ in 1..COUNTDOWN_LABELS.len() {
+            assert!(!scene.run_clock(step));
+        }
+
+        assert!(scene.run_clock(Duration::from_millis(400)));
+        assert!(scene.run_clock(Duration::from_millis(400)));
+        assert_eq!(scene.results().duration, Duration::from_millis(800));
+    }
+
+    #[test]
+    fn tab_is_never_a_steering_key() {
+        // Tab switches the path assist, alongside holding H for it.
+        assert!(InputMap::PRESETS
+            .iter()
+            .all(|preset| !preset.binds(KeyCode::Tab)));
+        assert!(!InputMap::NAMED_KEYS.contains(&KeyCode::Tab));
+    }
+
+    #[test]
+    fn grid_sizes_are_bounded() {
+        assert!(GridConfig::parse("40x20").is_some());
+        assert!(GridConfig::parse("100x100").is_some());
+        assert!(GridConfig::parse("1x20").is_none());
+        assert!(GridConfig::parse("101x20").is_none());
+        assert!(GridConfig::parse("20x100000").is_none());
+    }
+
+    #[test]
+    fn fruit_only_lands_on_free_cells() {
+        let mut scene = scene_on(GridConfig::new(5, 5).unwrap());
+        scene.snakes[0]
+            .bodyparts
+            .push_front(Position { x: 2, y: 3 });
+        scene.obstacles =
+            HashSet::from([Position { x: 0, y: 0 }, Position { x: 4, y: 1 }]);
+        scene
+            .fruits
+            .push(Fruit::random(Position { x: 1, y: 1 }, BASE_TICK_MS));
+
+        // 25 cells, less two for the snake, two walls and the first fruit.
+        for _ in 0..20 {
+            assert!(scene.place_fruit());
+        }
+        assert!(!scene.place_fruit());
+        assert_eq!(scene.new_fruit_position(&[]), None);
+
+        let cells: HashSet<Position> =
+            scene.fruits.iter().map(|fruit| fruit.pos).collect();
+        assert_eq!(cells.len(), scene.fruits.len());
+        assert!(cells.iter().all(|cell| {
+            !scene.occupied_by_snake(cell) && !scene.obstacles.contains(cell)
+        }));
+    }
+
+    #[test]
+    fn eating_the_last_fruit_on_a_full_grid_wins() {
+        // The snake fills the bottom row, with its head on the right
+        // facing up at the last fruit and a wall in the last free cell.
+        let mut scene = scene_on(GridConfig::new(2, 2).unwrap());
+        let snake = &mut scene.snakes[0];
+        snake.bodyparts =
+            VecDeque::from([Position { x: 0, y: 1 }, Position { x: 1, y: 1 }]);
+        snake.head_position = Position { x: 1, y: 1 };
+        snake.direction = Direction::Up;
+        scene.obstacles = HashSet::from([Position { x: 0, y: 0 }]);
+        scene.fruits.push(Fruit {
+            telegraph_ticks_left: 0,
+            ..Fruit::random(Position { x: 1, y: 0 }, BASE_TICK_MS)
+        });
+
+        assert!(matches!(
+            scene.step(),
+            Some(SwapScene::GameOver(RunResults { won: true, .. }))
+        ));
+    }
+
+    #[test]
+    fn save_and_load_restore_the_snake_and_fruit() {
+        let mut scene = empty_scene();
+        scene
+            .fruits
+            .push(Fruit::random(Position { x: 1, y: 1 }, BASE_TICK_MS));
+        scene.snakes[0].score = 12;
+        let saved = scene.save();
+
+        for _ in 0..3 {
+            scene.step();
+        }
+        scene.snakes[0].score = 0;
+        scene.fruits.clear();
+        scene.load(saved);
+
+        let snake = &scene.snakes[0];
+        assert_eq!(snake.score, 12);
+        assert_eq!(snake.head_position, empty_scene().snakes[0].head_position);
+        assert_eq!(snake.bodyparts, empty_scene().snakes[0].bodyparts);
+        assert_eq!(scene.fruits[0].pos, Position { x: 1, y: 1 });
+    }
+
+    #[test]
+    fn telegraph_covers_half_a_second_at_any_speed() {
+        assert_eq!(Fruit::telegraph_ticks(350), 2);
+        assert_eq!(Fruit::telegraph_ticks(BASE_TICK_MS), 2);
+        assert_eq!(Fruit::telegraph_ticks(150), 4);
+        // Sped up to the limit and then boosted.
+        assert_eq!(Fruit::telegraph_ticks(MIN_TICK_MS / 2), 17);
+        assert_eq!(Fruit::telegraph_ticks(1000), 1);
+    }
+
+    #[test]
+    fn fruit_reached_before_telegraph_ends_moves() {
+        for tick_ms in TICK_SPEEDS {
+            let scene =
+                arrive_after(tick_ms, Fruit::telegraph_ticks(tick_ms) - 1);
+            assert_eq!(scene.fruits_eaten, 0);
+            assert_eq!(scene.fruits.len(), 1);
+            assert_ne!(scene.fruits[0].pos, scene.snakes[0].head_position);
+        }
+    }
+
+    #[test]
+    fn fruit_reached_as_telegraph_ends_is_eaten() {
+        for tick_ms in TICK_SPEEDS {
+            let scene = arrive_after(tick_ms, Fruit::telegraph_ticks(tick_ms));
+            assert_eq!(scene.fruits_eaten, 1);
+        }
+    }
+
+    #[test]
+    fn fruit_reached_after_telegraph_ends_is_eaten() {
+        for tick_ms in TICK_SPEEDS {
+            let scene =
+                arrive_after(tick_ms, Fruit::telegraph_ticks(tick_ms) + 1);
+            assert_eq!(scene.fruits_eaten, 1);
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::Position;
+
+/// The shortest way from `start` to `goal`, a cell at a time and keeping
+/// off `blocked`. The path is the cells stepped on after `start`, ending
+/// with `goal`, or `None` if there's no way through.
+pub fn bfs(
+    start: Position,
+    goal: Position,
+    blocked: &[Position],
+    grid_width: i32,
+    grid_height: i32,
+) -> Option<Vec<Position>> {
+    let blocked: HashSet<&Position> = blocked.iter().collect();
+    // Each cell reached so far, with the one it was reached from.
+    let mut came_from: HashMap<Position, Position> = HashMap::new();
+    let mut queue = VecDeque::from([start]);
+
+    while let Some(cell) = queue.pop_front() {
+        if cell == goal {
+            let mut path = Vec::new();
+            let mut cell = goal;
+            while cell != start {
+                let previous = came_from.remove(&cell)?;
+                path.push(cell);
+                cell = previous;
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        for next in cell
+            .neighbors(grid_width, grid_height)
+            .into_iter()
+            .flatten()
+        {
+            if next != start
+                && !blocked.contains(&next)
+                && !came_from.contains_key(&next)
+            {
+                queue.push_back(next);
+                came_from.insert(next, cell);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(x: i32, y: i32) -> Position {
+        Position { x, y }
+    }
+
+    /// Each step in `path` is to a cell next to the one before it.
+    fn is_connected(start: Position, path: &[Position]) -> bool {
+        std::iter::once(&start)
+            .chain(path)
+            .zip(path)
+            .all(|(a, b)| a.manhattan_distance(b) == 1)
+    }
+
+    #[test]
+    fn shortest_path_on_open_grid() {
+        let path = bfs(at(0, 0), at(3, 2), &[], 5, 5).unwrap();
+        assert_eq!(path.len(), 5);
+        assert_eq!(path.last(), Some(&at(3, 2)));
+        assert!(is_connected(at(0, 0), &path));
+    }
+
+    #[test]
+    fn routes_around_blocked_cells() {
+        // A wall down the middle with a gap at the bottom.
+        let wall: Vec<Position> = (0..4).map(|y| at(2, y)).collect();
+        let path = bfs(at(0, 0), at(4, 0), &wall, 5, 5).unwrap();
+        assert_eq!(path.len(), 12);
+        assert!(path.iter().all(|cell| !wall.contains(cell)));
+        assert!(is_connected(at(0, 0), &path));
+    }
+
+    #[test]
+    fn walled_off_goal_has_no_path() {
+        let wall = [at(3, 4), at(4, 3)];
+        assert_eq!(bfs(at(0, 0), at(4, 4), &wall, 5, 5), None);
+    }
+
+    #[test]
+    fn start_at_goal_is_an_empty_path() {
+        assert_eq!(bfs(at(2, 2), at(2, 2), &[], 5, 5), Some(Vec::new()));
+    }
+
+    #[test]
+    fn blocked_goal_has_no_path() {
+        assert_eq!(bfs(at(0, 0), at(2, 0), &[at(2, 0)], 5, 5), None);
+    }
+}
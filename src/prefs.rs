@@ -0,0 +1,78 @@
+use std::{io, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::storage;
+
+const PATH: &str = "snek.toml";
+
+/// Preferences read from `snek.toml` in the working directory. Anything
+/// left out keeps the game's built-in default.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct UserPrefs {
+    pub grid_width: Option<i32>,
+    pub grid_height: Option<i32>,
+    /// Start fullscreen, as last toggled with F11.
+    pub fullscreen: Option<bool>,
+    /// Checkerboard behind the game, as last toggled with F2.
+    pub checkerboard: Option<bool>,
+    /// Hold the menu starfield still and don't flash a crashed snake.
+    pub reduce_motion: Option<bool>,
+    /// Name of a colour theme: `"Default"`, `"Colorblind"` or `"Contrast"`.
+    pub theme: Option<String>,
+    pub tick_speed_ms: Option<u64>,
+    pub wrap_mode: Option<bool>,
+    /// Steer towards the mouse while the left button is held.
+    pub mouse_steering: Option<bool>,
+    /// Walls inside the arena.
+    pub walls: Option<bool>,
+    /// Walls scattered at random when `walls` is on.
+    pub obstacle_count: Option<usize>,
+    /// Pairs of portals scattered at random.
+    pub portal_count: Option<usize>,
+    /// Shrink the play area as the run goes on.
+    pub shrinking_grid: Option<bool>,
+    /// `"Classic"`, `"Timed"` or `"Quota"`.
+    pub mode: Option<String>,
+    pub fruit_count: Option<usize>,
+    /// Name of a bundled level, such as `"Rooms"`.
+    pub level: Option<String>,
+    /// A key preset: `"Both"`, `"WASD"`, `"Arrows"` or `"Vim"`.
+    pub controls: Option<String>,
+    /// Fruit eaten between poison fruit, or 0 for none.
+    pub poison_fruit_every: Option<u32>,
+    /// Who the last run from the menu was played with: `"Solo"`,
+    /// `"2 Players"` or `"Vs Computer"`. Quick play starts it again.
+    pub last_run: Option<String>,
+    /// Key names such as `"W"` or `"Up"`.
+    pub key_up: Option<String>,
+    pub key_down: Option<String>,
+    pub key_left: Option<String>,
+    pub key_right: Option<String>,
+}
+
+impl UserPrefs {
+    /// Reads the preferences file. A missing file is the same as an empty
+    /// one, and a broken one is reported and ignored.
+    pub fn load() -> Self {
+        match storage::read(Path::new(PATH)) {
+            Ok(contents) => toml::from_str(&contents).unwrap_or_else(|e| {
+                eprintln!("Ignoring {PATH}: {e}");
+                Self::default()
+            }),
+            Err(e) => {
+                if e.kind() != io::ErrorKind::NotFound {
+                    eprintln!("Failed to read {PATH}: {e}");
+                }
+                Self::default()
+            }
+        }
+    }
+
+    /// Writes the preferences out, leaving unset ones out of the file.
+    pub fn save(&self) -> io::Result<()> {
+        let contents = toml::to_string(self).map_err(io::Error::other)?;
+        storage::write(Path::new(PATH), &contents)
+    }
+}
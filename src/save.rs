@@ -0,0 +1,256 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    fmt, io,
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{storage, Direction, GridConfig, Position};
+
+const PATH: &str = "save.json";
+
+/// A snapshot of a one player run, written to `save.json` with F5 and read
+/// back with F9.
+#[derive(Deserialize, Serialize)]
+pub struct SaveState {
+    /// Tail first, ending with the head.
+    pub bodyparts: VecDeque<Position>,
+    pub head_position: Position,
+    pub fruit_locations: Vec<Position>,
+    pub score: u32,
+    pub direction: Direction,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    /// A saved cell that isn't on the current grid.
+    OutOfBounds {
+        x: i32,
+        y: i32,
+    },
+    EmptyBody,
+    /// The head isn't the last part of the body.
+    HeadNotOnBody,
+    /// A cell with two things in it, counting the walls already on the
+    /// grid.
+    Overlap {
+        x: i32,
+        y: i32,
+    },
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::Json(e) => write!(f, "{e}"),
+            Self::OutOfBounds { x, y } => {
+                write!(f, "({x}, {y}) is off the grid")
+            }
+            Self::EmptyBody => write!(f, "the snake has no body"),
+            Self::HeadNotOnBody => {
+                write!(f, "the head isn't at the end of the body")
+            }
+            Self::Overlap { x, y } => {
+                write!(f, "more than one thing is at ({x}, {y})")
+            }
+        }
+    }
+}
+
+impl From<io::Error> for SaveError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
+
+impl SaveState {
+    /// Reads the last save, which has to fit on `grid` around `walls` to
+    /// be used.
+    pub fn load(
+        grid: &GridConfig,
+        walls: &HashSet<Position>,
+    ) -> Result<Self, SaveError> {
+        let contents = storage::read(Path::new(PATH))?;
+        Self::from_json(&contents, grid, walls)
+    }
+
+    pub fn save(&self) -> Result<(), SaveError> {
+        storage::write(Path::new(PATH), &self.to_json()?)?;
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> Result<String, SaveError> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Parses a save and checks it describes a snake that could be
+    /// playing: on the grid, in one piece and not on top of itself, the
+    /// fruit or `walls`.
+    pub fn from_json(
+        contents: &str,
+        grid: &GridConfig,
+        walls: &HashSet<Position>,
+    ) -> Result<Self, SaveError> {
+        let state: Self = serde_json::from_str(contents)?;
+
+        if state.bodyparts.back() != Some(&state.head_position) {
+            return Err(if state.bodyparts.is_empty() {
+                SaveError::EmptyBody
+            } else {
+                SaveError::HeadNotOnBody
+            });
+        }
+
+        let mut seen = HashSet::new();
+        for cell in state.bodyparts.iter().chain(&state.fruit_locations) {
+            if !(0..grid.width).contains(&cell.x)
+                || !(0..grid.height).contains(&cell.y)
+            {
+                return Err(SaveError::OutOfBounds {
+                    x: cell.x,
+                    y: cell.y,
+                });
+            }
+            if walls.contains(cell) || !seen.insert(cell) {
+                return Err(SaveError::Overlap {
+                    x: cell.x,
+                    y: cell.y,
+                });
+            }
+        }
+
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(x: i32, y: i32) -> Position {
+        Position { x, y }
+    }
+
+    /// A three long snake heading right along the top row, with a fruit
+    /// below it.
+    fn state() -> SaveState {
+        SaveState {
+            bodyparts: VecDeque::from([at(0, 0), at(1, 0), at(2, 0)]),
+            head_position: at(2, 0),
+            fruit_locations: vec![at(2, 2)],
+            score: 7,
+            direction: Direction::Right,
+        }
+    }
+
+    fn check(state: &SaveState) -> Result<SaveState, SaveError> {
+        let grid = GridConfig::new(5, 5).unwrap();
+        let walls = HashSet::from([at(4, 4)]);
+        SaveState::from_json(&state.to_json().unwrap(), &grid, &walls)
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let loaded = check(&state()).unwrap();
+        assert_eq!(loaded.bodyparts, state().bodyparts);
+        assert_eq!(loaded.head_position, state().head_position);
+        assert_eq!(loaded.fruit_locations, state().fruit_locations);
+        assert_eq!(loaded.score, 7);
+        assert_eq!(loaded.direction, Direction::Right);
+    }
+
+    #[test]
+    fn rejects_broken_json() {
+        let grid = GridConfig::new(5, 5).unwrap();
+        assert!(matches!(
+            SaveState::from_json("{", &grid, &HashSet::new()),
+            Err(SaveError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_empty_body() {
+        let state = SaveState {
+            bodyparts: VecDeque::new(),
+            ..state()
+        };
+        assert!(matches!(check(&state), Err(SaveError::EmptyBody)));
+    }
+
+    #[test]
+    fn rejects_head_off_the_body() {
+        let state = SaveState {
+            head_position: at(3, 0),
+            ..state()
+        };
+        assert!(matches!(check(&state), Err(SaveError::HeadNotOnBody)));
+    }
+
+    #[test]
+    fn rejects_cells_off_the_grid() {
+        let state = SaveState {
+            fruit_locations: vec![at(5, 0)],
+            ..state()
+        };
+        assert!(matches!(
+            check(&state),
+            Err(SaveError::OutOfBounds { x: 5, y: 0 })
+        ));
+    }
+
+    #[test]
+    fn rejects_a_snake_crossing_itself() {
+        let state = SaveState {
+            bodyparts: VecDeque::from([at(1, 0), at(0, 0), at(1, 0)]),
+            head_position: at(1, 0),
+            ..state()
+        };
+        assert!(matches!(
+            check(&state),
+            Err(SaveError::Overlap { x: 1, y: 0 })
+        ));
+    }
+
+    #[test]
+    fn rejects_fruit_on_the_snake_or_another_fruit() {
+        let on_snake = SaveState {
+            fruit_locations: vec![at(1, 0)],
+            ..state()
+        };
+        assert!(matches!(
+            check(&on_snake),
+            Err(SaveError::Overlap { x: 1, y: 0 })
+        ));
+
+        let doubled = SaveState {
+            fruit_locations: vec![at(3, 3), at(3, 3)],
+            ..state()
+        };
+        assert!(matches!(
+            check(&doubled),
+            Err(SaveError::Overlap { x: 3, y: 3 })
+        ));
+    }
+
+    #[test]
+    fn rejects_cells_on_walls() {
+        let state = SaveState {
+            fruit_locations: vec![at(4, 4)],
+            ..state()
+        };
+        assert!(matches!(
+            check(&state),
+            Err(SaveError::Overlap { x: 4, y: 4 })
+        ));
+    }
+}
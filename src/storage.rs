@@ -0,0 +1,105 @@
+//! Somewhere to keep small text files between runs: the filesystem on
+//! desktop, and `localStorage` in the browser with the `web-storage`
+//! feature.
+
+use std::{io, path::Path};
+
+/// Reads what was last written to `path`. Nothing having been written
+/// yet is an `io::ErrorKind::NotFound` error on every backend.
+pub fn read(path: &Path) -> io::Result<String> {
+    backend::read(path)
+}
+
+pub fn write(path: &Path, contents: &str) -> io::Result<()> {
+    backend::write(path, contents)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod backend {
+    use std::{fs, io, path::Path};
+
+    pub fn read(path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    /// Creates any directories missing on the way.
+    pub fn write(path: &Path, contents: &str) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, contents)
+    }
+}
+
+/// Each path is used as is for its key. The JavaScript half is in
+/// `web/snek_storage.js`.
+#[cfg(all(target_arch = "wasm32", feature = "web-storage"))]
+mod backend {
+    use std::{io, path::Path, ptr};
+
+    extern "C" {
+        /// Copies the value stored under the key into the buffer if it
+        /// fits, returning its length in bytes or -1 if there is none.
+        fn snek_storage_get(
+            key: *const u8,
+            key_len: usize,
+            buf: *mut u8,
+            buf_len: usize,
+        ) -> i32;
+        fn snek_storage_set(
+            key: *const u8,
+            key_len: usize,
+            value: *const u8,
+            value_len: usize,
+        );
+    }
+
+    pub fn read(path: &Path) -> io::Result<String> {
+        let key = path.to_string_lossy();
+
+        // SAFETY: the key outlives the call and nothing is written to an
+        // empty buffer.
+        let len = unsafe {
+            snek_storage_get(key.as_ptr(), key.len(), ptr::null_mut(), 0)
+        };
+        let len = usize::try_from(len)
+            .map_err(|_| io::Error::from(io::ErrorKind::NotFound))?;
+
+        let mut buf = vec![0; len];
+        // SAFETY: the buffer is exactly as long as the value.
+        unsafe {
+            snek_storage_get(key.as_ptr(), key.len(), buf.as_mut_ptr(), len);
+        }
+        String::from_utf8(buf)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn write(path: &Path, contents: &str) -> io::Result<()> {
+        let key = path.to_string_lossy();
+
+        // SAFETY: both strings outlive the call and are only read.
+        unsafe {
+            snek_storage_set(
+                key.as_ptr(),
+                key.len(),
+                contents.as_ptr(),
+                contents.len(),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Without `web-storage` the browser build starts afresh on every visit.
+#[cfg(all(target_arch = "wasm32", not(feature = "web-storage")))]
+mod backend {
+    use std::{io, path::Path};
+
+    pub fn read(_path: &Path) -> io::Result<String> {
+        Err(io::ErrorKind::NotFound.into())
+    }
+
+    pub fn write(_path: &Path, _contents: &str) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
@@ -0,0 +1,201 @@
+use macroquad::prelude::{
+    Color, BLACK, BLUE, DARKGRAY, DARKPURPLE, GOLD, GRAY, GREEN, LIGHTGRAY,
+    MAROON, ORANGE, PURPLE, RED, SKYBLUE, WHITE, YELLOW,
+};
+
+use crate::cycle;
+
+/// How one snake is coloured.
+#[derive(Clone, Copy, PartialEq)]
+pub struct SnakeColors {
+    pub head: Color,
+    /// The body next to the head, which fades to `tail` further back.
+    pub body: Color,
+    pub tail: Color,
+}
+
+/// Every colour the game is drawn in, so the whole look can be swapped at
+/// once.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub name: &'static str,
+    pub background: Color,
+    /// The two shades of the checkerboard behind the game.
+    pub checker: [Color; 2],
+    /// Player one, player two and the computer.
+    pub snakes: [SnakeColors; 3],
+    pub eyes: Color,
+    /// Apples, berries and melons.
+    pub fruits: [Color; 3],
+    pub golden_fruit: Color,
+    pub poison_fruit: Color,
+    pub speedboost: Color,
+    pub obstacle: Color,
+    pub portal: Color,
+    pub text: Color,
+    /// Combos and new records.
+    pub highlight: Color,
+    /// Wins and quotas that have been met.
+    pub good: Color,
+    /// Quotas that haven't been met yet.
+    pub warning: Color,
+    /// The edge of a shrinking play area, the last few seconds and errors.
+    pub danger: Color,
+    pub button: Color,
+    pub button_hovered: Color,
+    pub button_pressed: Color,
+    pub button_text: Color,
+    pub button_shortcut: Color,
+    pub focus: Color,
+    /// Drawn round cells and buttons, if anything is.
+    pub outline: Option<Color>,
+}
+
+const DEFAULT: Theme = Theme {
+    name: "Default",
+    background: BLACK,
+    checker: [
+        Color::new(0.05, 0.05, 0.05, 1.),
+        Color::new(0.1, 0.1, 0.1, 1.),
+    ],
+    snakes: [
+        SnakeColors {
+            head: Color::new(0.8, 1., 0.8, 1.),
+            body: GREEN,
+            tail: Color::new(0., 0.35, 0.08, 1.),
+        },
+        SnakeColors {
+            head: Color::new(0.8, 0.8, 1., 1.),
+            body: BLUE,
+            tail: Color::new(0., 0.18, 0.4, 1.),
+        },
+        // Darker than an apple so the two aren't mixed up.
+        SnakeColors {
+            head: Color::new(1., 0.8, 0.8, 1.),
+            body: MAROON,
+            tail: Color::new(0.3, 0.05, 0.09, 1.),
+        },
+    ],
+    eyes: BLACK,
+    fruits: [RED, PURPLE, ORANGE],
+    golden_fruit: GOLD,
+    poison_fruit: DARKPURPLE,
+    speedboost: SKYBLUE,
+    obstacle: DARKGRAY,
+    portal: Color::new(0., 1., 1., 1.),
+    text: WHITE,
+    highlight: GOLD,
+    good: GREEN,
+    warning: ORANGE,
+    // Pinker than an apple, so the crash ring and the fruit can't be mixed
+    // up.
+    danger: Color::new(1., 0.2, 0.6, 1.),
+    button: WHITE,
+    button_hovered: LIGHTGRAY,
+    button_pressed: GRAY,
+    button_text: GREEN,
+    button_shortcut: DARKGRAY,
+    focus: YELLOW,
+    outline: None,
+};
+
+/// Built from the Okabe-Ito palette, which keeps blue and orange apart for
+/// the common kinds of colour blindness instead of leaning on red and
+/// green.
+const COLORBLIND: Theme = Theme {
+    name: "Colorblind",
+    snakes: [
+        SnakeColors {
+            head: Color::new(0.75, 0.9, 1., 1.),
+            body: Color::new(0., 0.45, 0.7, 1.),
+            tail: Color::new(0., 0.18, 0.3, 1.),
+        },
+        SnakeColors {
+            head: Color::new(1., 0.9, 0.7, 1.),
+            body: Color::new(0.9, 0.62, 0., 1.),
+            tail: Color::new(0.4, 0.27, 0., 1.),
+        },
+        SnakeColors {
+            head: Color::new(0.95, 0.85, 0.9, 1.),
+            body: Color::new(0.8, 0.47, 0.65, 1.),
+            tail: Color::new(0.35, 0.2, 0.28, 1.),
+        },
+    ],
+    fruits: [
+        Color::new(0.84, 0.37, 0., 1.),
+        Color::new(0., 0.62, 0.45, 1.),
+        Color::new(0.94, 0.89, 0.26, 1.),
+    ],
+    golden_fruit: WHITE,
+    speedboost: Color::new(0.34, 0.71, 0.91, 1.),
+    portal: LIGHTGRAY,
+    highlight: Color::new(0.94, 0.89, 0.26, 1.),
+    good: Color::new(0.34, 0.71, 0.91, 1.),
+    warning: Color::new(0.9, 0.62, 0., 1.),
+    danger: Color::new(1., 0.2, 0.5, 1.),
+    button_text: Color::new(0., 0.45, 0.7, 1.),
+    focus: Color::new(0.9, 0.62, 0., 1.),
+    ..DEFAULT
+};
+
+/// Bright colours on black, with everything outlined in white.
+const HIGH_CONTRAST: Theme = Theme {
+    name: "Contrast",
+    checker: [BLACK, Color::new(0.15, 0.15, 0.15, 1.)],
+    snakes: [
+        SnakeColors {
+            head: WHITE,
+            body: Color::new(0., 1., 0., 1.),
+            tail: Color::new(0., 0.5, 0., 1.),
+        },
+        SnakeColors {
+            head: WHITE,
+            body: Color::new(0., 0.85, 1., 1.),
+            tail: Color::new(0., 0.4, 0.5, 1.),
+        },
+        SnakeColors {
+            head: WHITE,
+            body: Color::new(1., 0.3, 1., 1.),
+            tail: Color::new(0.5, 0.1, 0.5, 1.),
+        },
+    ],
+    fruits: [
+        Color::new(1., 0.15, 0.15, 1.),
+        Color::new(1., 0.55, 0., 1.),
+        YELLOW,
+    ],
+    poison_fruit: Color::new(0.6, 0., 1., 1.),
+    speedboost: Color::new(1., 0.6, 0.85, 1.),
+    obstacle: GRAY,
+    portal: Color::new(0.3, 0.3, 1., 1.),
+    highlight: YELLOW,
+    good: Color::new(0., 1., 0., 1.),
+    danger: Color::new(1., 0., 0.55, 1.),
+    button: BLACK,
+    button_hovered: Color::new(0.25, 0.25, 0.25, 1.),
+    button_pressed: Color::new(0.45, 0.45, 0.45, 1.),
+    button_text: WHITE,
+    button_shortcut: YELLOW,
+    outline: Some(WHITE),
+    ..DEFAULT
+};
+
+impl Theme {
+    pub const ALL: [Self; 3] = [DEFAULT, COLORBLIND, HIGH_CONTRAST];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|theme| theme.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn next(&self) -> Self {
+        cycle(&Self::ALL, self)
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        DEFAULT
+    }
+}
@@ -0,0 +1,33 @@
+// localStorage for the wasm build, used by src/storage.rs. Load this after
+// mq_js_bundle.js and before calling load().
+miniquad_add_plugin({
+    name: "snek_storage",
+    version: 1,
+    register_plugin: function (importObject) {
+        const decoder = new TextDecoder();
+        const encoder = new TextEncoder();
+
+        function readString(ptr, len) {
+            return decoder.decode(new Uint8Array(wasm_memory.buffer, ptr, len));
+        }
+
+        importObject.env.snek_storage_get = function (key, keyLen, buf, bufLen) {
+            const value = window.localStorage.getItem(readString(key, keyLen));
+            if (value === null) {
+                return -1;
+            }
+            const bytes = encoder.encode(value);
+            if (bytes.length <= bufLen) {
+                new Uint8Array(wasm_memory.buffer, buf, bytes.length).set(bytes);
+            }
+            return bytes.length;
+        };
+
+        importObject.env.snek_storage_set = function (key, keyLen, value, valueLen) {
+            window.localStorage.setItem(
+                readString(key, keyLen),
+                readString(value, valueLen),
+            );
+        };
+    },
+});